@@ -0,0 +1,992 @@
+use crate::check::{check_banners, check_nicknames, check_students};
+use crate::collection::ElephTable;
+use crate::language::display_name;
+use crate::lookup::{lookup_in, Lookup};
+use crate::student::Profile;
+use blue_gacha::banner::{Banner, BannerBuilder};
+use blue_gacha::gacha::{GachaBuilder, Rarity};
+use blue_gacha::i18n::{I18nString, Language};
+use blue_gacha::student::Student;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration as StdDuration, SystemTime};
+
+const DEFAULT_DATA_DIR: &str = "data";
+const WATCH_INTERVAL: StdDuration = StdDuration::from_secs(10);
+
+/// The rates of every rarity used when a banner doesn't override them, in percent
+pub const DEFAULT_RATES: Rates = Rates {
+    one: 79.0,
+    two: 18.5,
+    three: 2.5,
+};
+
+lazy_static! {
+    static ref DATA: RwLock<Option<Data>> = RwLock::new(None);
+}
+
+/// Everything read from the data directory. [`init`] loads it, and [`reload`] swaps it out as a whole
+#[derive(Clone)]
+struct Data {
+    students: Arc<Vec<StudentInfo>>,
+    nicknames: Arc<Nicknames>,
+    registry: Arc<Registry>,
+    eleph: Arc<ElephTable>,
+}
+
+impl Data {
+    fn load() -> Result<Self, BannerError> {
+        let students = load_students(&data_dir().join("students.json"))?;
+        let nicknames = load_nicknames(&data_dir().join("nicknames.json"))?;
+        if let Some(name) = nicknames
+            .keys()
+            .find(|name| find_info(&students, name).is_none())
+        {
+            return Err(BannerError::UnknownNickname(name.clone()));
+        }
+        let registry = Registry::load(&students)?;
+        let eleph = load_eleph_table(&data_dir().join("eleph.json"))?;
+
+        Ok(Self {
+            students: Arc::new(students),
+            nicknames: Arc::new(nicknames),
+            registry: Arc::new(registry),
+            eleph: Arc::new(eleph),
+        })
+    }
+
+    /// Everything `arona-check` would report about this data
+    fn problems(&self) -> Vec<String> {
+        let banners: Vec<BannerInfo> = self
+            .registry
+            .banners
+            .iter()
+            .map(|entry| entry.info.clone())
+            .collect();
+
+        let mut problems = Vec::new();
+        check_students(&self.students, &mut problems);
+        check_nicknames(&self.nicknames, &self.students, &mut problems);
+        check_banners(&banners, Some(&self.students), &mut problems);
+        problems
+    }
+}
+
+/// The game's servers, which have their own rosters and banner schedules
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Region {
+    #[default]
+    #[serde(rename = "jp")]
+    Japan,
+    #[serde(rename = "global")]
+    Global,
+}
+
+impl Region {
+    /// Parses a region as it is written in commands, e.g. `jp` or `global`
+    pub fn parse(word: &str) -> Option<Region> {
+        match word.to_lowercase().as_str() {
+            "jp" | "jpn" | "japan" => Some(Region::Japan),
+            "global" | "gl" => Some(Region::Global),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Region::Japan => write!(f, "JP"),
+            Region::Global => write!(f, "Global"),
+        }
+    }
+}
+
+/// The other names students are known by, keyed by their Japanese name, as described in
+/// `data/nicknames.json`
+pub type Nicknames = HashMap<String, Vec<String>>;
+
+/// A student as they are described in `data/students.json`
+#[derive(Clone, Deserialize)]
+pub struct StudentInfo {
+    #[serde(flatten)]
+    pub student: Student,
+    /// Limited students are left out of standard pools, so only banners which list them have them
+    #[serde(default)]
+    pub limited: bool,
+    /// The day the student was released on each server. Students without any are on every server
+    #[serde(default)]
+    pub release: HashMap<Region, NaiveDate>,
+    /// The Japanese name of the student this one is an alternate version of, e.g. a swimsuit version
+    #[serde(default)]
+    pub base: Option<String>,
+    /// What the student's portrait and page are stored under, e.g. `Hina_Swimsuit`.
+    /// Defaults to their English name, which only works for students who aren't variants
+    #[serde(default)]
+    pub image: Option<String>,
+    #[serde(flatten)]
+    pub profile: Profile,
+}
+
+impl StudentInfo {
+    /// What the student's portrait and page are stored under. See [`StudentInfo::image`]
+    pub fn image_key(&self) -> String {
+        self.image
+            .clone()
+            .or_else(|| self.student.name.get(Language::English))
+            .unwrap_or_else(|| self.student.name.to_string())
+    }
+
+    /// Whether the student can be recruited on `region`'s server at `time`
+    pub fn is_released(&self, region: Region, time: DateTime<Utc>) -> bool {
+        if self.release.is_empty() {
+            return true;
+        }
+
+        self.release
+            .get(&region)
+            .map(|date| *date <= time.naive_utc().date())
+            .unwrap_or(false)
+    }
+}
+
+/// A Banner as it is described in `data/banners/*.json`
+#[derive(Clone, Deserialize)]
+pub struct BannerInfo {
+    pub id: String,
+    pub name: I18nString,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub image: Option<String>,
+    /// The server the banner runs on
+    #[serde(default)]
+    pub region: Region,
+    /// Festival banners are marked as such wherever the banner is shown
+    #[serde(default)]
+    pub festival: bool,
+    /// Overrides [`DEFAULT_RATES`], e.g. for the doubled 3★ rate of a festival
+    #[serde(default)]
+    pub rates: Option<Rates>,
+    pub pool: Pool,
+    #[serde(default)]
+    pub priority: Vec<PriorityInfo>,
+    #[serde(default)]
+    pub sparkable: Vec<String>,
+}
+
+/// Japanese names of every student in a banner's pool, split by rarity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Pool {
+    /// Whether every student in students.json who isn't limited is in the pool too
+    #[serde(default)]
+    pub standard: bool,
+    #[serde(default)]
+    pub three: Vec<String>,
+    #[serde(default)]
+    pub two: Vec<String>,
+    #[serde(default)]
+    pub one: Vec<String>,
+}
+
+impl Pool {
+    /// Adds the standard students released on `region` by `time` to the pool if it asks for them
+    pub fn resolve(&mut self, students: &[StudentInfo], region: Region, time: DateTime<Utc>) {
+        if !self.standard {
+            return;
+        }
+
+        let standard = students
+            .iter()
+            .filter(|info| !info.limited && info.is_released(region, time));
+        for info in standard {
+            let name = info.student.name.to_string();
+            let listed = match info.student.rarity {
+                Rarity::One => &mut self.one,
+                Rarity::Two => &mut self.two,
+                Rarity::Three => &mut self.three,
+            };
+
+            if !listed.contains(&name) {
+                listed.push(name);
+            }
+        }
+    }
+
+    /// The Japanese names of every student of `rarity` in the pool
+    pub fn of(&self, rarity: Rarity) -> &[String] {
+        match rarity {
+            Rarity::One => &self.one,
+            Rarity::Two => &self.two,
+            Rarity::Three => &self.three,
+        }
+    }
+
+    /// The rarity the student called `name` is listed under
+    pub fn rarity_of(&self, name: &str) -> Option<Rarity> {
+        [Rarity::Three, Rarity::Two, Rarity::One]
+            .iter()
+            .copied()
+            .find(|&rarity| self.of(rarity).iter().any(|student| student == name))
+    }
+
+    /// The chance (in percent) of pulling the student called `name` when rarities are rolled with
+    /// `rates`, on a banner with the rate-ups `priority` and the usual rates `base`.
+    ///
+    /// Rate-up students keep the rate they were given, while whatever is left of their rarity's
+    /// rate is split evenly between every other student of that rarity. A rarity with a boosted
+    /// rate (like the last pull of a 10-roll) scales its rate-up students by the same amount.
+    pub fn student_rate(
+        &self,
+        priority: &[PriorityInfo],
+        base: Rates,
+        name: &str,
+        rates: Rates,
+    ) -> Option<f32> {
+        let rarity = self.rarity_of(name)?;
+        let base_rate = base.of(rarity);
+        let scale = if base_rate > 0.0 {
+            rates.of(rarity) / base_rate
+        } else {
+            0.0
+        };
+
+        if let Some(priority) = priority.iter().find(|priority| priority.name == name) {
+            return Some(priority.rate * scale);
+        }
+
+        let pool = self.of(rarity);
+        let mut rate_ups: Vec<&PriorityInfo> = Vec::new();
+        for priority in priority
+            .iter()
+            .filter(|priority| pool.contains(&priority.name))
+        {
+            // A student listed twice only takes their rate once, like in the lookup above
+            if !rate_ups.iter().any(|rate_up| rate_up.name == priority.name) {
+                rate_ups.push(priority);
+            }
+        }
+
+        let remaining_rate = base_rate - rate_ups.iter().map(|priority| priority.rate).sum::<f32>();
+        let others = pool.len() - rate_ups.len();
+
+        Some(remaining_rate / others as f32 * scale)
+    }
+}
+
+/// The chance of pulling a student of each rarity, in percent
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Rates {
+    pub one: f32,
+    pub two: f32,
+    pub three: f32,
+}
+
+impl Rates {
+    pub fn of(&self, rarity: Rarity) -> f32 {
+        match rarity {
+            Rarity::One => self.one,
+            Rarity::Two => self.two,
+            Rarity::Three => self.three,
+        }
+    }
+
+    /// The rates of the last pull of a 10-roll, where every 1★ is upgraded to a 2★
+    pub fn guaranteed(&self) -> Rates {
+        Rates {
+            one: 0.0,
+            two: self.one + self.two,
+            three: self.three,
+        }
+    }
+}
+
+/// A rate-up student and their individual rate (in percent)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PriorityInfo {
+    pub name: String,
+    pub rate: f32,
+}
+
+#[derive(Debug)]
+pub enum BannerError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, serde_json::Error),
+    UnknownPriority(String, String),
+    UnknownSparkable(String, String),
+    UnknownStudent(String, String),
+    Invalid(String),
+    Empty(PathBuf),
+    NoStudents(PathBuf),
+    UnknownNickname(String),
+    /// Everything [`crate::check`] found wrong with the data directory
+    Problems(Vec<String>),
+}
+
+impl fmt::Display for BannerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BannerError::Io(path, err) => write!(f, "Failed to read {}: {}", path.display(), err),
+            BannerError::Parse(path, err) => {
+                write!(f, "Failed to parse {}: {}", path.display(), err)
+            }
+            BannerError::UnknownPriority(id, name) => {
+                write!(f, "Rate-up student {} of {} is not in the pool", name, id)
+            }
+            BannerError::UnknownSparkable(id, name) => {
+                write!(f, "Sparkable student {} of {} is not in the pool", name, id)
+            }
+            BannerError::UnknownStudent(id, name) => {
+                write!(f, "Student {} of {} is not in students.json", name, id)
+            }
+            BannerError::Invalid(id) => write!(f, "blue-gacha rejected the banner {}", id),
+            BannerError::Empty(path) => write!(f, "No banners were found in {}", path.display()),
+            BannerError::NoStudents(path) => {
+                write!(f, "No students were found in {}", path.display())
+            }
+            BannerError::UnknownNickname(name) => {
+                write!(f, "{} has nicknames but is not in students.json", name)
+            }
+            BannerError::Problems(problems) => {
+                // Keep the reply to !reload within Discord's message limit
+                write!(f, "{}", problems[..problems.len().min(5)].join("; "))?;
+                if problems.len() > 5 {
+                    write!(f, " and {} more problems", problems.len() - 5)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for BannerError {}
+
+impl BannerInfo {
+    pub fn rates(&self) -> Rates {
+        self.rates.unwrap_or(DEFAULT_RATES)
+    }
+
+    /// Whether the student with the Japanese name `name` can be pulled from this banner
+    pub fn contains(&self, name: &str) -> bool {
+        self.pool
+            .three
+            .iter()
+            .chain(self.pool.two.iter())
+            .chain(self.pool.one.iter())
+            .any(|student| student == name)
+    }
+
+    /// The Japanese names of every student of `rarity` in the pool
+    pub fn pool_of(&self, rarity: Rarity) -> &[String] {
+        self.pool.of(rarity)
+    }
+
+    /// The rarity the student called `name` is listed under in the pool
+    pub fn rarity_of(&self, name: &str) -> Option<Rarity> {
+        self.pool.rarity_of(name)
+    }
+
+    /// The chance (in percent) of pulling the student called `name` when rarities are rolled with
+    /// `rates`. See [`Pool::student_rate`]
+    pub fn student_rate(&self, name: &str, rates: Rates) -> Option<f32> {
+        self.pool
+            .student_rate(&self.priority, self.rates(), name, rates)
+    }
+
+    /// Whether `student` is one of this banner's rate-up students
+    pub fn is_rate_up(&self, student: &Student) -> bool {
+        self.priority
+            .iter()
+            .any(|priority| student.name == priority.name.as_str())
+    }
+
+    /// Builds a blue-gacha Banner out of this definition.
+    ///
+    /// Pool entries which can't be found in `students` are logged and dropped,
+    /// while rate-up or sparkable students that are missing from the pool are an error.
+    pub fn build(&self, students: &[StudentInfo]) -> Result<Banner, BannerError> {
+        let mut pool = Vec::new();
+        pool.extend(get_students(students, &self.pool.three));
+        pool.extend(get_students(students, &self.pool.two));
+        pool.extend(get_students(students, &self.pool.one));
+
+        let mut priority = Vec::with_capacity(self.priority.len());
+        for info in self.priority.iter() {
+            let student = find_in_pool(&pool, &info.name)
+                .ok_or_else(|| BannerError::UnknownPriority(self.id.clone(), info.name.clone()))?;
+
+            priority.push(student.into_priority_student(info.rate));
+        }
+
+        let mut sparkable = Vec::with_capacity(self.sparkable.len());
+        for name in self.sparkable.iter() {
+            let student = find_in_pool(&pool, name)
+                .ok_or_else(|| BannerError::UnknownSparkable(self.id.clone(), name.clone()))?;
+
+            sparkable.push(student);
+        }
+
+        let rates = self.rates();
+        let mut gacha = GachaBuilder::new(rates.one, rates.two, rates.three).with_pool(pool);
+        if !priority.is_empty() {
+            gacha = gacha.with_priority(priority);
+        }
+        let gacha = gacha
+            .finish()
+            .ok_or_else(|| BannerError::Invalid(self.id.clone()))?;
+
+        let mut builder = BannerBuilder::new(&self.name.to_string()).with_gacha(gacha);
+        if let Some(eng) = self.name.get(Language::English) {
+            builder = builder.with_name_translation(Language::English, &eng);
+        }
+        if !sparkable.is_empty() {
+            builder = builder.with_sparkable_students(sparkable);
+        }
+
+        builder
+            .finish()
+            .ok_or_else(|| BannerError::Invalid(self.id.clone()))
+    }
+}
+
+/// The directory arona reads its data files from. Can be overridden with `ARONA_DATA_DIR`
+pub fn data_dir() -> PathBuf {
+    env::var("ARONA_DATA_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_DATA_DIR))
+}
+
+/// Reads the list of every student, e.g. `data/students.json`
+pub fn load_students(path: &Path) -> Result<Vec<StudentInfo>, BannerError> {
+    let text = fs::read_to_string(path).map_err(|err| BannerError::Io(path.to_path_buf(), err))?;
+    let students: Vec<StudentInfo> =
+        serde_json::from_str(&text).map_err(|err| BannerError::Parse(path.to_path_buf(), err))?;
+
+    if students.is_empty() {
+        return Err(BannerError::NoStudents(path.to_path_buf()));
+    }
+
+    info!("Loaded {} students from {}", students.len(), path.display());
+    Ok(students)
+}
+
+/// Reads the nicknames of students, e.g. `data/nicknames.json`. The file is optional
+pub fn load_nicknames(path: &Path) -> Result<Nicknames, BannerError> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Nicknames::new()),
+        Err(err) => return Err(BannerError::Io(path.to_path_buf(), err)),
+    };
+    let nicknames: Nicknames =
+        serde_json::from_str(&text).map_err(|err| BannerError::Parse(path.to_path_buf(), err))?;
+
+    info!(
+        "Loaded the nicknames of {} students from {}",
+        nicknames.len(),
+        path.display()
+    );
+    Ok(nicknames)
+}
+
+/// Reads the Eleph rates, e.g. `data/eleph.json`
+pub fn load_eleph_table(path: &Path) -> Result<ElephTable, BannerError> {
+    let text = fs::read_to_string(path).map_err(|err| BannerError::Io(path.to_path_buf(), err))?;
+
+    serde_json::from_str(&text).map_err(|err| BannerError::Parse(path.to_path_buf(), err))
+}
+
+/// Reads every banner definition in `dir`, ordered by file name.
+///
+/// Files are prefixed with the date the banner started on (e.g. `2021-04-08-midori.json`)
+/// to keep the directory readable, though the schedule itself comes from `start` and `end`.
+pub fn load_banners(dir: &Path) -> Result<Vec<BannerInfo>, BannerError> {
+    let entries = fs::read_dir(dir).map_err(|err| BannerError::Io(dir.to_path_buf(), err))?;
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let path = entry
+            .map_err(|err| BannerError::Io(dir.to_path_buf(), err))?
+            .path();
+
+        if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    let mut banners = Vec::with_capacity(paths.len());
+    for path in paths {
+        let text = fs::read_to_string(&path).map_err(|err| BannerError::Io(path.clone(), err))?;
+        let info: BannerInfo =
+            serde_json::from_str(&text).map_err(|err| BannerError::Parse(path.clone(), err))?;
+
+        info!("Loaded banner {} from {}", info.id, path.display());
+        banners.push(info);
+    }
+
+    Ok(banners)
+}
+
+/// A banner definition along with the blue-gacha Banner built from it
+pub struct BannerEntry {
+    pub info: BannerInfo,
+    pub banner: Banner,
+}
+
+impl BannerEntry {
+    /// Whether the banner is running at `now`
+    pub fn is_live(&self, now: DateTime<Utc>) -> bool {
+        self.info.start <= now && now < self.info.end
+    }
+
+    /// The rate-up students of this banner formatted as e.g. `ミドリ (Midori)` in Japanese
+    pub fn rate_up_names(&self, language: Language) -> Vec<String> {
+        let names = self.info.priority.iter().map(|priority| &priority.name);
+        display_names(names, language)
+    }
+
+    /// The students who can be sparked on this banner, formatted like [`Self::rate_up_names`]
+    pub fn sparkable_names(&self, language: Language) -> Vec<String> {
+        display_names(self.info.sparkable.iter(), language)
+    }
+
+    /// Finds one of this banner's sparkable students by any of their names. See [`crate::lookup::lookup`]
+    pub fn find_sparkable(&self, query: &str) -> Option<Student> {
+        let students = all_students();
+        let sparkable: Vec<StudentInfo> = self
+            .info
+            .sparkable
+            .iter()
+            .filter_map(|name| find_info(&students, name).cloned())
+            .collect();
+
+        match lookup_in(&sparkable, &all_nicknames(), query) {
+            Lookup::Found(info) => Some(info.student),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        let query = query.trim();
+        let eng_matches = self
+            .banner
+            .name
+            .get(Language::English)
+            .map(|eng| eng.eq_ignore_ascii_case(query))
+            .unwrap_or(false);
+
+        self.info.id.eq_ignore_ascii_case(query) || self.banner.name == query || eng_matches
+    }
+}
+
+/// Every banner found in the data directory, ordered by start date
+pub struct Registry {
+    banners: Vec<Arc<BannerEntry>>,
+}
+
+impl Registry {
+    /// Loads every banner in the data directory and builds them out of `students`
+    pub fn load(students: &[StudentInfo]) -> Result<Self, BannerError> {
+        let dir = data_dir().join("banners");
+        let infos = load_banners(&dir)?;
+
+        if infos.is_empty() {
+            return Err(BannerError::Empty(dir));
+        }
+
+        let mut banners = Vec::with_capacity(infos.len());
+        for mut info in infos {
+            info.pool.resolve(students, info.region, info.start);
+            let banner = info.build(students)?;
+            banners.push(Arc::new(BannerEntry { info, banner }));
+        }
+        banners.sort_by_key(|entry| entry.info.start);
+
+        Ok(Self { banners })
+    }
+
+    /// Banners which are running on `region`'s server at `now`, oldest first
+    pub fn live(
+        &self,
+        now: DateTime<Utc>,
+        region: Region,
+    ) -> impl Iterator<Item = &Arc<BannerEntry>> {
+        self.banners
+            .iter()
+            .filter(move |entry| entry.info.region == region && entry.is_live(now))
+    }
+
+    /// Banners of `region`'s server which ended before `now`, newest first
+    pub fn archived(
+        &self,
+        now: DateTime<Utc>,
+        region: Region,
+    ) -> impl Iterator<Item = &Arc<BannerEntry>> {
+        self.banners
+            .iter()
+            .rev()
+            .filter(move |entry| entry.info.region == region && entry.info.end <= now)
+    }
+
+    /// The next banner to start on `region`'s server after `now`
+    pub fn upcoming(&self, now: DateTime<Utc>, region: Region) -> Option<&Arc<BannerEntry>> {
+        self.banners
+            .iter()
+            .find(|entry| entry.info.region == region && entry.info.start > now)
+    }
+
+    /// The newest live banner of `region`. If nothing is running, the most recently started banner
+    /// is used instead, or the first one to start if none have yet. `None` if `region` has no banners
+    pub fn default_banner(&self, now: DateTime<Utc>, region: Region) -> Option<&Arc<BannerEntry>> {
+        self.live(now, region)
+            .last()
+            .or_else(|| {
+                self.banners
+                    .iter()
+                    .rev()
+                    .find(|entry| entry.info.region == region && entry.info.start <= now)
+            })
+            .or_else(|| {
+                self.banners
+                    .iter()
+                    .find(|entry| entry.info.region == region)
+            })
+    }
+
+    /// Finds any banner by its id
+    pub fn get(&self, id: &str) -> Option<&Arc<BannerEntry>> {
+        self.banners.iter().find(|entry| entry.info.id == id)
+    }
+
+    /// Finds a live or archived banner of `region` by its id, Japanese name or English translation.
+    ///
+    /// Live banners take precedence so that a rerun is picked over its original run.
+    /// Banners of the other server can still be picked by their id.
+    pub fn find(
+        &self,
+        query: &str,
+        now: DateTime<Utc>,
+        region: Region,
+    ) -> Option<&Arc<BannerEntry>> {
+        self.live(now, region)
+            .find(|entry| entry.matches(query))
+            .or_else(|| {
+                self.archived(now, region)
+                    .find(|entry| entry.matches(query))
+            })
+            .or_else(|| {
+                self.banners
+                    .iter()
+                    .find(|entry| entry.info.start <= now && entry.info.id == query.trim())
+            })
+    }
+
+    /// Picks the banner requested in a command, falling back to the default banner of `region`.
+    /// `None` if there's no such banner, or no query and no banners on `region`
+    pub fn select(
+        &self,
+        query: &str,
+        now: DateTime<Utc>,
+        region: Region,
+    ) -> Option<&Arc<BannerEntry>> {
+        if query.trim().is_empty() {
+            self.default_banner(now, region)
+        } else {
+            self.find(query, now, region)
+        }
+    }
+}
+
+/// Every banner arona knows about
+pub fn registry() -> Arc<Registry> {
+    data().registry
+}
+
+/// Loads the students, banners and Eleph rates from the data directory. Has to be called before
+/// the bot starts
+pub fn init() -> Result<(), BannerError> {
+    let data = Data::load()?;
+    for problem in data.problems() {
+        warn!("{}", problem);
+    }
+    info!(
+        "Loaded {} students and {} banners",
+        data.students.len(),
+        data.registry.banners.len()
+    );
+
+    swap(data);
+    Ok(())
+}
+
+/// Loads the students, banners and Eleph rates from the data directory again and swaps them in.
+///
+/// Unlike at startup, where they are only logged, anything `arona-check` reports (e.g. a pool with
+/// students missing from students.json) is refused, so the current data is kept whenever anything
+/// is wrong with the new one.
+/// Returns how many students and banners were loaded.
+pub fn reload() -> Result<(usize, usize), BannerError> {
+    let data = Data::load()?;
+    let problems = data.problems();
+    if !problems.is_empty() {
+        return Err(BannerError::Problems(problems));
+    }
+
+    let counts = (data.students.len(), data.registry.banners.len());
+    swap(data);
+
+    info!("Reloaded {} students and {} banners", counts.0, counts.1);
+    Ok(counts)
+}
+
+/// Reloads the data directory whenever a file in it changes. Runs forever
+pub async fn watch() {
+    let mut last_modified = modified(&data_dir());
+
+    loop {
+        tokio::time::sleep(WATCH_INTERVAL).await;
+
+        let modified = modified(&data_dir());
+        if modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+
+        info!("The data directory changed, reloading it");
+        if let Err(err) = reload() {
+            warn!("Kept the current data: {}", err);
+        }
+    }
+}
+
+/// The most recent modification time of any file in `dir` and the number of files in it,
+/// so that deleted files are noticed too
+fn modified(dir: &Path) -> (Option<SystemTime>, usize) {
+    let mut latest = None;
+    let mut count = 0;
+
+    let paths = fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path());
+    for path in paths {
+        let (modified, files) = if path.is_dir() {
+            modified(&path)
+        } else {
+            (fs::metadata(&path).and_then(|meta| meta.modified()).ok(), 1)
+        };
+
+        latest = latest.max(modified);
+        count += files;
+    }
+
+    (latest, count)
+}
+
+fn data() -> Data {
+    let data = match DATA.read() {
+        Ok(data) => data.clone(),
+        Err(poisoned) => {
+            error!("The data's lock was poisoned");
+            poisoned.into_inner().clone()
+        }
+    };
+
+    data.expect("The data directory is loaded by banner::init before it is used")
+}
+
+fn swap(data: Data) {
+    match DATA.write() {
+        Ok(mut current) => *current = Some(data),
+        Err(poisoned) => {
+            error!("The data's lock was poisoned");
+            *poisoned.into_inner() = Some(data);
+        }
+    }
+}
+
+/// Formats a duration as e.g. `3d 4h 12m`
+pub fn format_duration(duration: Duration) -> String {
+    let minutes = duration.num_minutes().max(0);
+    let (days, hours, minutes) = (minutes / (60 * 24), (minutes / 60) % 24, minutes % 60);
+
+    if days > 0 {
+        format!("{}d {}h {}m", days, hours, minutes)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+/// Every student in students.json
+pub fn all_students() -> Arc<Vec<StudentInfo>> {
+    data().students
+}
+
+/// Every nickname in nicknames.json
+pub fn all_nicknames() -> Arc<Nicknames> {
+    data().nicknames
+}
+
+/// The Eleph rates in eleph.json
+pub fn eleph_table() -> Arc<ElephTable> {
+    data().eleph
+}
+
+/// Formats students given by their Japanese names with [`display_name`], leaving names missing
+/// from students.json as they are
+pub fn display_names<'a>(
+    names: impl Iterator<Item = &'a String>,
+    language: Language,
+) -> Vec<String> {
+    names
+        .map(|name| match find_student(name) {
+            Some(student) => display_name(&student.name, language),
+            None => name.clone(),
+        })
+        .collect()
+}
+
+/// Looks up a student in students.json by their Japanese name
+pub fn find_student(name: &str) -> Option<Student> {
+    find_info(&all_students(), name).map(|info| info.student.clone())
+}
+
+/// What a student's portrait and page are stored under. See [`StudentInfo::image`]
+pub fn image_key(student: &Student) -> String {
+    match find_info(&all_students(), &student.name.to_string()) {
+        Some(info) => info.image_key(),
+        None => student
+            .name
+            .get(Language::English)
+            .unwrap_or_else(|| student.name.to_string()),
+    }
+}
+
+fn find_in_pool(pool: &[Student], name: &str) -> Option<Student> {
+    pool.iter().find(|student| student.name == name).cloned()
+}
+
+fn find_info<'a>(students: &'a [StudentInfo], name: &str) -> Option<&'a StudentInfo> {
+    students.iter().find(|info| info.student.name == name)
+}
+
+fn get_students(all: &[StudentInfo], names: &[String]) -> Vec<Student> {
+    let mut students = Vec::with_capacity(names.len());
+
+    for name in names {
+        match find_info(all, name) {
+            Some(info) => {
+                students.push(info.student.clone());
+            }
+            None => error!("Could not find {} in students.json", name),
+        };
+    }
+
+    students
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn banner(festival_rates: Option<&str>) -> BannerInfo {
+        let rates = festival_rates
+            .map(|rates| format!(r#""rates": {},"#, rates))
+            .unwrap_or_default();
+
+        serde_json::from_str(&format!(
+            r#"{{
+                "id": "test",
+                "name": {{ "translations": {{ "jpn": "テスト", "eng": "Test" }} }},
+                "start": "2021-02-04T11:00:00+09:00",
+                "end": "2021-02-18T11:00:00+09:00",
+                {}
+                "pool": {{
+                    "three": ["ホシノ", "シロコ", "ヒナ", "イオリ"],
+                    "two": ["アカリ", "ジュンコ"],
+                    "one": ["チナツ"]
+                }},
+                "priority": [{{ "name": "ホシノ", "rate": 0.7 }}]
+            }}"#,
+            rates
+        ))
+        .unwrap()
+    }
+
+    fn assert_close(actual: Option<f32>, expected: f32) {
+        let actual = actual.expect("the student should be in the pool");
+        assert!(
+            (actual - expected).abs() < 1e-4,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn rate_ups_keep_their_rate() {
+        let info = banner(None);
+        assert_close(info.student_rate("ホシノ", info.rates()), 0.7);
+    }
+
+    #[test]
+    fn the_rest_of_a_rarity_is_split_evenly() {
+        let info = banner(None);
+
+        // (2.5 - 0.7) / 3
+        assert_close(info.student_rate("シロコ", info.rates()), 0.6);
+        assert_close(info.student_rate("アカリ", info.rates()), 9.25);
+        assert_close(info.student_rate("チナツ", info.rates()), 79.0);
+    }
+
+    #[test]
+    fn boosted_rates_scale_every_student() {
+        let info = banner(None);
+        let guaranteed = info.rates().guaranteed();
+
+        assert_close(info.student_rate("ホシノ", guaranteed), 0.7);
+        assert_close(info.student_rate("アカリ", guaranteed), 48.75);
+        assert_close(info.student_rate("チナツ", guaranteed), 0.0);
+    }
+
+    #[test]
+    fn festival_rates_override_the_defaults() {
+        let info = banner(Some(r#"{ "one": 76.5, "two": 18.5, "three": 5.0 }"#));
+
+        // (5.0 - 0.7) / 3
+        assert_close(info.student_rate("ヒナ", info.rates()), 4.3 / 3.0);
+    }
+
+    #[test]
+    fn duplicate_rate_ups_count_once() {
+        let mut info = banner(None);
+        info.priority.push(info.priority[0].clone());
+
+        assert_close(info.student_rate("ホシノ", info.rates()), 0.7);
+        assert_close(info.student_rate("シロコ", info.rates()), 0.6);
+    }
+
+    #[test]
+    fn language_names_are_not_regions() {
+        assert_eq!(Region::parse("global"), Some(Region::Global));
+        assert_eq!(Region::parse("JP"), Some(Region::Japan));
+        assert_eq!(Region::parse("en"), None);
+    }
+
+    #[test]
+    fn students_outside_the_pool_have_no_rate() {
+        let info = banner(None);
+        assert_eq!(info.student_rate("アル", info.rates()), None);
+    }
+}
@@ -0,0 +1,82 @@
+//! Validates students.json and every banner definition without starting the bot.
+//!
+//! Usage: `arona-check [data directory]`. The directory defaults to `ARONA_DATA_DIR` or `./data`,
+//! and the exit code is non-zero if any problem was found.
+
+use arona::banner::{data_dir, load_banners, load_eleph_table, load_nicknames, load_students};
+use arona::check::{check_banners, check_nicknames, check_students};
+use dotenv::dotenv;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+fn main() {
+    dotenv().ok();
+
+    let dir = env::args()
+        .nth(1)
+        .map(PathBuf::from)
+        .unwrap_or_else(data_dir);
+    let mut errors = Vec::new();
+
+    let students_path = dir.join("students.json");
+    // Banners are only checked against students.json if it could be loaded
+    let students = match load_students(&students_path) {
+        Ok(students) => {
+            check_students(&students, &mut errors);
+            Some(students)
+        }
+        Err(err) => {
+            errors.push(err.to_string());
+            None
+        }
+    };
+
+    let nicknames_path = dir.join("nicknames.json");
+    match load_nicknames(&nicknames_path) {
+        Ok(nicknames) => {
+            if let Some(students) = &students {
+                check_nicknames(&nicknames, students, &mut errors);
+            }
+        }
+        Err(err) => errors.push(err.to_string()),
+    }
+
+    let banners_path = dir.join("banners");
+    let banners = match load_banners(&banners_path) {
+        Ok(banners) if banners.is_empty() => {
+            errors.push(format!(
+                "No banners were found in {}",
+                banners_path.display()
+            ));
+            banners
+        }
+        Ok(banners) => banners,
+        Err(err) => {
+            errors.push(err.to_string());
+            Vec::new()
+        }
+    };
+
+    check_banners(&banners, students.as_deref(), &mut errors);
+
+    if let Err(err) = load_eleph_table(&dir.join("eleph.json")) {
+        errors.push(err.to_string());
+    }
+
+    for error in errors.iter() {
+        eprintln!("error: {}", error);
+    }
+
+    if errors.is_empty() {
+        println!(
+            "{} students and {} banners in {} are valid",
+            students.map(|students| students.len()).unwrap_or(0),
+            banners.len(),
+            dir.display()
+        );
+    } else {
+        eprintln!("Found {} problems in {}", errors.len(), dir.display());
+        process::exit(1);
+    }
+}
@@ -0,0 +1,415 @@
+//! Checks the data directory for mistakes which loading it doesn't catch, like duplicate students or
+//! rate-ups adding up to more than their rarity's rate. Used by `arona-check` and [`reload`].
+//!
+//! [`reload`]: crate::banner::reload
+
+use crate::banner::{BannerError, BannerInfo, Nicknames, StudentInfo};
+use crate::lookup::normalize;
+use blue_gacha::gacha::Rarity;
+use blue_gacha::i18n::Language;
+use std::collections::{HashMap, HashSet};
+
+/// Reports students which are listed twice, have no English name, or are broken variants
+pub fn check_students(students: &[StudentInfo], errors: &mut Vec<String>) {
+    let mut seen = HashSet::new();
+
+    for student in students.iter().map(|info| &info.student) {
+        let name = student.name.to_string();
+
+        if !seen.insert(name.clone()) {
+            errors.push(format!("{} is in students.json more than once", name));
+        }
+
+        let has_english = student
+            .name
+            .get(Language::English)
+            .map(|eng| !eng.trim().is_empty())
+            .unwrap_or(false);
+        if !has_english {
+            errors.push(format!("{} has no English translation", name));
+        }
+    }
+
+    let mut image_keys = HashMap::new();
+    for info in students.iter() {
+        let name = info.student.name.to_string();
+
+        if let Some(base) = &info.base {
+            match students
+                .iter()
+                .find(|student| student.student.name == base.as_str())
+            {
+                Some(student) if student.base.is_some() => errors.push(format!(
+                    "{} is a variant of {}, which is a variant itself",
+                    name, base
+                )),
+                Some(_) => {}
+                None => errors.push(format!(
+                    "{} is a variant of {}, who is not in students.json",
+                    name, base
+                )),
+            }
+
+            // The English name of a variant is no good as a URL
+            if info.image.is_none() {
+                errors.push(format!("{} is a variant but has no image key", name));
+            }
+        }
+
+        if let Some(other) = image_keys.insert(info.image_key(), name.clone()) {
+            errors.push(format!(
+                "{} and {} have the same image key {}",
+                other,
+                name,
+                info.image_key()
+            ));
+        }
+    }
+}
+
+/// Reports nicknames of unknown students, and nicknames which are already someone else's name
+pub fn check_nicknames(nicknames: &Nicknames, students: &[StudentInfo], errors: &mut Vec<String>) {
+    // Who every name (in its normalized form) belongs to, starting with their real names
+    let mut owners: HashMap<String, String> = HashMap::new();
+    for student in students.iter().map(|info| &info.student) {
+        let jpn = student.name.to_string();
+        owners.insert(normalize(&jpn), jpn.clone());
+        if let Some(eng) = student.name.get(Language::English) {
+            owners.insert(normalize(&eng), jpn);
+        }
+    }
+
+    for (jpn, names) in nicknames.iter() {
+        if !students
+            .iter()
+            .any(|info| info.student.name == jpn.as_str())
+        {
+            errors.push(format!("{} has nicknames but is not in students.json", jpn));
+        }
+
+        for name in names.iter() {
+            match owners.get(&normalize(name)) {
+                Some(owner) if owner != jpn => errors.push(format!(
+                    "The nickname {} of {} is already a name of {}",
+                    name, jpn, owner
+                )),
+                Some(_) => {}
+                None => {
+                    owners.insert(normalize(name), jpn.clone());
+                }
+            }
+        }
+    }
+}
+
+/// Reports every banner defined more than once, along with the problems [`check_banner`] finds.
+/// Pools are resolved first, so standard students are checked too
+pub fn check_banners(
+    banners: &[BannerInfo],
+    students: Option<&[StudentInfo]>,
+    errors: &mut Vec<String>,
+) {
+    let mut ids = HashSet::new();
+    for info in banners.iter() {
+        if !ids.insert(info.id.as_str()) {
+            errors.push(format!("Banner {} is defined more than once", info.id));
+        }
+
+        let mut info = info.clone();
+        if let Some(students) = students {
+            info.pool.resolve(students, info.region, info.start);
+        }
+        check_banner(&info, students, errors);
+    }
+}
+
+/// Reports mistakes in a banner's rates, pool, rate-ups and sparkable students. The pool is only
+/// checked against students.json when `students` is given
+pub fn check_banner(info: &BannerInfo, students: Option<&[StudentInfo]>, errors: &mut Vec<String>) {
+    let id = &info.id;
+    let rates = info.rates();
+
+    let total_rate = rates.one + rates.two + rates.three;
+    if (total_rate - 100.0).abs() > 0.001 {
+        errors.push(format!(
+            "The rates of {} add up to {}% instead of 100%",
+            id, total_rate
+        ));
+    }
+
+    if info.name.get(Language::English).is_none() {
+        errors.push(format!("Banner {} has no English translation", id));
+    }
+
+    if info.start >= info.end {
+        errors.push(format!("Banner {} ends before it starts", id));
+    }
+
+    let mut listed = HashMap::new();
+    for &rarity in [Rarity::Three, Rarity::Two, Rarity::One].iter() {
+        for name in info.pool_of(rarity) {
+            if listed.insert(name.as_str(), rarity).is_some() {
+                errors.push(format!("{} is in the pool of {} more than once", name, id));
+            }
+
+            let students = match students {
+                Some(students) => students,
+                None => continue,
+            };
+
+            match students
+                .iter()
+                .find(|student| student.student.name == name.as_str())
+            {
+                Some(student) if student.student.rarity != rarity => errors.push(format!(
+                    "{} is listed as {} in the pool of {}, but is {} in students.json",
+                    name,
+                    stars(rarity),
+                    id,
+                    stars(student.student.rarity)
+                )),
+                Some(student) if !student.is_released(info.region, info.end) => {
+                    errors.push(format!(
+                        "{} in the pool of {} isn't released on the {} server before it ends",
+                        name, id, info.region
+                    ))
+                }
+                Some(_) => {}
+                None => errors.push(format!(
+                    "{} in the pool of {} is not in students.json",
+                    name, id
+                )),
+            }
+        }
+    }
+
+    let mut rate_ups = HashSet::new();
+    for priority in info.priority.iter() {
+        if !rate_ups.insert(priority.name.as_str()) {
+            errors.push(format!(
+                "{} is a rate-up of {} more than once",
+                priority.name, id
+            ));
+        }
+
+        if !listed.contains_key(priority.name.as_str()) {
+            errors.push(format!(
+                "Rate-up student {} of {} is not in the pool",
+                priority.name, id
+            ));
+        }
+    }
+
+    for &rarity in [Rarity::Three, Rarity::Two, Rarity::One].iter() {
+        let total: f32 = info
+            .priority
+            .iter()
+            .filter(|priority| listed.get(priority.name.as_str()) == Some(&rarity))
+            .map(|priority| priority.rate)
+            .sum();
+
+        if total > rates.of(rarity) {
+            errors.push(format!(
+                "The {} rate-ups of {} add up to {}%, more than the {}% of their rarity",
+                stars(rarity),
+                id,
+                total,
+                rates.of(rarity)
+            ));
+        }
+    }
+
+    let mut sparkable = HashSet::new();
+    for name in info.sparkable.iter() {
+        if !sparkable.insert(name.as_str()) {
+            errors.push(format!("{} is sparkable on {} more than once", name, id));
+        }
+
+        if !listed.contains_key(name.as_str()) {
+            errors.push(format!(
+                "Sparkable student {} of {} is not in the pool",
+                name, id
+            ));
+        }
+    }
+
+    // Everything else build() can fail on has been reported above
+    if let Some(students) = students {
+        if let Err(err @ BannerError::Invalid(_)) = info.build(students) {
+            errors.push(err.to_string());
+        }
+    }
+}
+
+fn stars(rarity: Rarity) -> &'static str {
+    match rarity {
+        Rarity::One => "1★",
+        Rarity::Two => "2★",
+        Rarity::Three => "3★",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn students() -> Vec<StudentInfo> {
+        serde_json::from_str(
+            r#"[
+                { "name": { "translations": { "jpn": "ヒナ", "eng": "Hina" } }, "rarity": 3 },
+                { "name": { "translations": { "jpn": "イオリ", "eng": "Iori" } }, "rarity": 3 },
+                {
+                    "name": { "translations": { "jpn": "ヒナ（水着）", "eng": "Hina (Swimsuit)" } },
+                    "rarity": 3,
+                    "limited": true,
+                    "base": "ヒナ",
+                    "image": "Hina_Swimsuit"
+                },
+                { "name": { "translations": { "jpn": "アカリ", "eng": "Akari" } }, "rarity": 2 },
+                { "name": { "translations": { "jpn": "チナツ", "eng": "Chinatsu" } }, "rarity": 1 }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn banner() -> BannerInfo {
+        serde_json::from_str(
+            r#"{
+                "id": "test",
+                "name": { "translations": { "jpn": "テスト", "eng": "Test" } },
+                "start": "2021-07-28T11:00:00+09:00",
+                "end": "2021-08-11T11:00:00+09:00",
+                "pool": {
+                    "three": ["ヒナ（水着）", "ヒナ", "イオリ"],
+                    "two": ["アカリ"],
+                    "one": ["チナツ"]
+                },
+                "priority": [{ "name": "ヒナ（水着）", "rate": 0.7 }],
+                "sparkable": ["ヒナ（水着）"]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    fn student_problems(students: &[StudentInfo]) -> Vec<String> {
+        let mut errors = Vec::new();
+        check_students(students, &mut errors);
+        errors
+    }
+
+    fn banner_problems(info: &BannerInfo) -> Vec<String> {
+        let mut errors = Vec::new();
+        check_banner(info, Some(&students()), &mut errors);
+        errors
+    }
+
+    #[test]
+    fn valid_data_has_no_problems() {
+        assert!(student_problems(&students()).is_empty());
+        assert!(banner_problems(&banner()).is_empty());
+    }
+
+    #[test]
+    fn reports_duplicate_students() {
+        let mut students = students();
+        students.push(students[1].clone());
+
+        assert!(student_problems(&students)
+            .contains(&"イオリ is in students.json more than once".to_string()));
+    }
+
+    /// A 3★ variant of `base`, called `name` in both languages
+    fn variant(name: &str, base: &str, image: Option<&str>) -> StudentInfo {
+        let image = image
+            .map(|image| format!(r#", "image": "{}""#, image))
+            .unwrap_or_default();
+
+        serde_json::from_str(&format!(
+            r#"{{
+                "name": {{ "translations": {{ "jpn": "{0}", "eng": "{0}" }} }},
+                "rarity": 3,
+                "base": "{1}"{2}
+            }}"#,
+            name, base, image
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn reports_broken_variants() {
+        let mut students = students();
+        students.push(variant(
+            "ヒナ（ドレス）",
+            "ヒナ（水着）",
+            Some("Hina_Dress"),
+        ));
+        students.push(variant("アコ（ドレス）", "アコ", None));
+
+        let problems = student_problems(&students);
+        assert!(problems.contains(
+            &"ヒナ（ドレス） is a variant of ヒナ（水着）, which is a variant itself".to_string()
+        ));
+        assert!(problems.contains(
+            &"アコ（ドレス） is a variant of アコ, who is not in students.json".to_string()
+        ));
+        assert!(problems.contains(&"アコ（ドレス） is a variant but has no image key".to_string()));
+    }
+
+    #[test]
+    fn reports_duplicates_in_a_banner() {
+        let mut info = banner();
+        info.pool.one.push("チナツ".to_string());
+        info.priority.push(info.priority[0].clone());
+        info.sparkable.push("ヒナ（水着）".to_string());
+
+        let problems = banner_problems(&info);
+        assert!(problems.contains(&"チナツ is in the pool of test more than once".to_string()));
+        assert!(problems.contains(&"ヒナ（水着） is a rate-up of test more than once".to_string()));
+        assert!(problems.contains(&"ヒナ（水着） is sparkable on test more than once".to_string()));
+    }
+
+    #[test]
+    fn reports_rate_ups_above_their_rarity_rate() {
+        let mut info = banner();
+        info.priority[0].rate = 2.0;
+        let mut iori = info.priority[0].clone();
+        iori.name = "イオリ".to_string();
+        iori.rate = 1.0;
+        info.priority.push(iori);
+
+        assert!(banner_problems(&info).contains(
+            &"The 3★ rate-ups of test add up to 3%, more than the 2.5% of their rarity".to_string()
+        ));
+    }
+
+    #[test]
+    fn reports_sparkable_students_missing_from_the_pool() {
+        let mut info = banner();
+        info.sparkable.push("ホシノ".to_string());
+
+        assert!(banner_problems(&info)
+            .contains(&"Sparkable student ホシノ of test is not in the pool".to_string()));
+    }
+
+    #[test]
+    fn reports_students_of_the_wrong_rarity() {
+        let mut info = banner();
+        info.pool.one.retain(|name| name != "チナツ");
+        info.pool.two.push("チナツ".to_string());
+
+        assert!(banner_problems(&info).contains(
+            &"チナツ is listed as 2★ in the pool of test, but is 1★ in students.json".to_string()
+        ));
+    }
+
+    #[test]
+    fn reports_banners_defined_twice() {
+        let mut errors = Vec::new();
+        check_banners(&[banner(), banner()], Some(&students()), &mut errors);
+
+        assert_eq!(
+            errors,
+            vec!["Banner test is defined more than once".to_string()]
+        );
+    }
+}
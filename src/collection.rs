@@ -0,0 +1,179 @@
+use crate::banner::{all_students, eleph_table};
+use crate::general::BLUE_ARCHIVE_BLUE;
+use crate::language::{display_name, preferred_language, secondary, translate};
+use crate::lookup::select_student;
+use crate::recruitment::{get_rarity_stars, list_or_none};
+use crate::store::{self, rarity_level, UserData};
+use blue_gacha::gacha::Rarity;
+use blue_gacha::student::Student;
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::Deserialize;
+use serenity::client::Context;
+use serenity::framework::standard::{Args, CommandResult};
+use serenity::model::channel::Message;
+use std::collections::HashMap;
+
+const MAX_STARS: u8 = 5;
+
+/// The Eleph rates found in `data/eleph.json`
+#[derive(Deserialize)]
+pub struct ElephTable {
+    /// Eleph gained from a duplicate, keyed by the student's rarity
+    pub duplicate: HashMap<u8, u32>,
+    /// Eleph needed to reach a star level, keyed by that star level
+    pub star_up: HashMap<u8, u32>,
+}
+
+/// Adds a recruited student to a user's collection, converting duplicates into Eleph
+pub fn recruit(user: &mut UserData, student: &Student, now: DateTime<Utc>) {
+    if user.record(student, now) {
+        let rarity = rarity_level(student.rarity);
+        let eleph = eleph_table().duplicate.get(&rarity).copied().unwrap_or(0);
+
+        user.add_eleph(&student.name.to_string(), eleph);
+    }
+}
+
+pub async fn collection(ctx: &Context, msg: &Message) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+    info!("{} requested their collection", author_name);
+
+    let user = msg.mentions.first().unwrap_or(&msg.author);
+    let data = store::user(user.id);
+    let language = preferred_language(msg);
+
+    let all = all_students();
+    let students: Vec<&Student> = all.iter().map(|info| &info.student).collect();
+    let owned_count = students
+        .iter()
+        .filter(|student| data.collection.contains_key(&student.name.to_string()))
+        .count();
+    let completion = if students.is_empty() {
+        0.0
+    } else {
+        owned_count as f64 / students.len() as f64 * 100.0
+    };
+
+    let fields: Vec<(String, String, bool)> = [Rarity::Three, Rarity::Two, Rarity::One]
+        .iter()
+        .map(|&rarity| {
+            let of_rarity = students.iter().filter(|student| student.rarity == rarity);
+            let total = of_rarity.clone().count();
+
+            let owned: Vec<String> = of_rarity
+                .filter_map(|student| {
+                    let jpn = student.name.to_string();
+                    data.collection.get(&jpn).map(|owned| {
+                        format!(
+                            "{} ×{} {}★ ({} Eleph)",
+                            display_name(&student.name, language),
+                            owned.count,
+                            owned.stars(student.rarity),
+                            data.eleph(&jpn)
+                        )
+                    })
+                })
+                .collect();
+
+            let title = format!("{} ({}/{})", get_rarity_stars(rarity), owned.len(), total);
+            let value = if owned.is_empty() {
+                "None yet".to_string()
+            } else {
+                list_or_none(&owned)
+            };
+
+            (title, value, false)
+        })
+        .collect();
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!("{}'s Collection", user.name))
+                    .description(format!(
+                        "Completion: {}/{} ({:.1}%)",
+                        owned_count,
+                        students.len(),
+                        completion
+                    ))
+                    .fields(fields)
+                    .colour(BLUE_ARCHIVE_BLUE)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn starup(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+    let query = args.rest().trim();
+    info!("{} requested to star up {}", author_name, query);
+
+    let student = match select_student(ctx, msg, query).await? {
+        Some(info) => info.student,
+        None => return Ok(()),
+    };
+    let jpn = student.name.to_string();
+    let language = preferred_language(msg);
+    let name = translate(&student.name, language);
+
+    let result = store::update_user(msg.author.id, |user| {
+        let eleph = user.eleph(&jpn);
+        let owned = match user.collection.get_mut(&jpn) {
+            Some(owned) => owned,
+            None => return Err(format!("You haven't recruited {} yet", name)),
+        };
+
+        let stars = owned.stars(student.rarity);
+        if stars >= MAX_STARS {
+            return Err(format!("{} is already at {}★", name, MAX_STARS));
+        }
+
+        let cost = match eleph_table().star_up.get(&(stars + 1)) {
+            Some(&cost) => cost,
+            None => return Err(format!("There's no Eleph cost for {}★", stars + 1)),
+        };
+
+        if eleph < cost {
+            return Err(format!(
+                "{} needs {} Eleph to reach {}★, but you only have {}",
+                name,
+                cost,
+                stars + 1,
+                eleph
+            ));
+        }
+
+        owned.stars = stars + 1;
+        user.eleph.insert(jpn.clone(), eleph - cost);
+        Ok((stars + 1, cost, eleph - cost))
+    });
+
+    let (stars, cost, remaining) = match result {
+        Ok(result) => result,
+        Err(reason) => {
+            msg.reply(ctx, reason).await?;
+            return Ok(());
+        }
+    };
+    info!("{} raised {} to {} stars", author_name, jpn, stars);
+
+    let secondary_name = secondary(&student.name, language).unwrap_or_default();
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!("{} is now {}★", name, stars))
+                    .description(format!("{} used {} Eleph", secondary_name, cost))
+                    .field("Remaining Eleph", remaining, true)
+                    .colour(BLUE_ARCHIVE_BLUE)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,249 @@
+use crate::general::BLUE_ARCHIVE_BLUE;
+use crate::store;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use log::info;
+use serenity::client::Context;
+use serenity::framework::standard::{Args, CommandResult};
+use serenity::model::channel::Message;
+
+pub const ROLL_COST: u32 = 120;
+pub const ROLL10_COST: u32 = 1200;
+const DAILY_PYROXENE: u32 = 600;
+
+/// The outcome of paying for a roll
+pub enum Charge {
+    /// The economy is disabled where the roll was requested
+    Free,
+    /// The roll was paid for, leaving this much pyroxene
+    Paid(u32),
+    /// The user only has this much pyroxene
+    Insufficient(u32),
+}
+
+/// Whether rolls cost pyroxene where `msg` was sent. Rolls in DMs are always free
+pub fn is_enabled(msg: &Message) -> bool {
+    msg.guild_id
+        .map(|guild_id| store::guild(guild_id).economy)
+        .unwrap_or(false)
+}
+
+/// Takes `cost` pyroxene from the author of `msg` if the economy is enabled
+pub fn charge(msg: &Message, cost: u32) -> Charge {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) if store::guild(guild_id).economy => guild_id,
+        _ => return Charge::Free,
+    };
+
+    store::update_user(msg.author.id, |user| {
+        let wallet = user.wallet(guild_id);
+        if wallet.pyroxene >= cost {
+            wallet.pyroxene -= cost;
+            Charge::Paid(wallet.pyroxene)
+        } else {
+            Charge::Insufficient(wallet.pyroxene)
+        }
+    })
+}
+
+/// Gives back pyroxene taken by [`charge`] when a roll couldn't be completed
+pub fn refund(msg: &Message, charge: &Charge, cost: u32) {
+    if let (Charge::Paid(_), Some(guild_id)) = (charge, msg.guild_id) {
+        store::update_user(msg.author.id, |user| {
+            let wallet = user.wallet(guild_id);
+            wallet.pyroxene = wallet.pyroxene.saturating_add(cost)
+        });
+    }
+}
+
+pub async fn balance(ctx: &Context, msg: &Message) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+    info!("{} requested their balance", author_name);
+
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let user = msg.mentions.first().unwrap_or(&msg.author);
+    let pyroxene = store::read(|store| {
+        store
+            .users
+            .get(&user.id.0)
+            .and_then(|data| data.wallets.get(&guild_id.0))
+            .map(|wallet| wallet.pyroxene)
+            .unwrap_or(0)
+    });
+
+    let status = if is_enabled(msg) {
+        format!(
+            "A roll costs {} and a 10-roll costs {}",
+            ROLL_COST, ROLL10_COST
+        )
+    } else {
+        "Rolls are free here".to_string()
+    };
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!("{}'s Pyroxene", user.name))
+                    .description(status)
+                    .field("Balance", pyroxene, true)
+                    .colour(BLUE_ARCHIVE_BLUE)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn daily(ctx: &Context, msg: &Message) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+    info!("{} requested their daily pyroxene", author_name);
+
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let now = Utc::now();
+    let result = store::update_user(msg.author.id, |user| {
+        let wallet = user.wallet(guild_id);
+        let claimed_today = wallet
+            .last_daily
+            .map(|last| game_day(last) == game_day(now))
+            .unwrap_or(false);
+
+        if claimed_today {
+            Err(wallet.pyroxene)
+        } else {
+            wallet.last_daily = Some(now);
+            wallet.pyroxene = wallet.pyroxene.saturating_add(DAILY_PYROXENE);
+            Ok(wallet.pyroxene)
+        }
+    });
+
+    match result {
+        Ok(pyroxene) => {
+            msg.reply(
+                ctx,
+                format!(
+                    "You received {} pyroxene! You now have {}",
+                    DAILY_PYROXENE, pyroxene
+                ),
+            )
+            .await?;
+        }
+        Err(pyroxene) => {
+            msg.reply(
+                ctx,
+                format!(
+                    "You've already claimed today's pyroxene (you have {}). Come back after the reset at 04:00 JST",
+                    pyroxene
+                ),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn grant(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let user = match msg.mentions.first() {
+        Some(user) => user,
+        None => {
+            msg.reply(ctx, "Mention the user you'd like to grant pyroxene to")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let amount = match args.iter::<i64>().find_map(|arg| arg.ok()) {
+        Some(amount) => amount,
+        None => {
+            msg.reply(ctx, "Tell アロナ how much pyroxene to grant")
+                .await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "{} granted {} pyroxene to {}",
+        author_name, amount, user.name
+    );
+
+    let pyroxene = store::update_user(user.id, |data| {
+        let wallet = data.wallet(guild_id);
+        let pyroxene = (wallet.pyroxene as i64)
+            .saturating_add(amount)
+            .clamp(0, u32::MAX as i64);
+        wallet.pyroxene = pyroxene as u32;
+        wallet.pyroxene
+    });
+
+    msg.reply(ctx, format!("{} now has {} pyroxene", user.name, pyroxene))
+        .await?;
+
+    Ok(())
+}
+
+pub async fn economy(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let enabled = match args.rest().trim().to_lowercase().as_str() {
+        "on" | "enable" => true,
+        "off" | "disable" => false,
+        _ => {
+            let status = if store::guild(guild_id).economy {
+                "on"
+            } else {
+                "off"
+            };
+            msg.reply(
+                ctx,
+                format!(
+                    "The economy is {}. Use `!economy on` or `!economy off`",
+                    status
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "{} set the economy of {} to {}",
+        author_name, guild_id.0, enabled
+    );
+
+    store::update_guild(guild_id, |settings| settings.economy = enabled);
+
+    let reply = if enabled {
+        format!(
+            "Rolls now cost pyroxene: {} for a roll and {} for a 10-roll",
+            ROLL_COST, ROLL10_COST
+        )
+    } else {
+        "Rolls are free again".to_string()
+    };
+    msg.reply(ctx, reply).await?;
+
+    Ok(())
+}
+
+/// The in-game day `time` falls on. Days reset at 04:00 JST (19:00 UTC)
+fn game_day(time: DateTime<Utc>) -> NaiveDate {
+    (time + Duration::hours(5)).naive_utc().date()
+}
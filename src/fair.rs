@@ -0,0 +1,511 @@
+use crate::banner::{display_names, find_student, registry, BannerInfo, Pool, PriorityInfo, Rates};
+use crate::collection::recruit;
+use crate::economy::{Charge, ROLL10_COST, ROLL_COST};
+use crate::general::BLUE_ARCHIVE_BLUE;
+use crate::language::{display_name, preferred_language, translate};
+use crate::recruitment::{
+    format_points, get_rarity_colour, get_rarity_stars, parse_count, pay, select_banner,
+};
+use crate::stats::record_roll;
+use crate::store::{self, FairRoll};
+use blue_gacha::gacha::Rarity;
+use blue_gacha::student::Student;
+use chrono::Utc;
+use log::info;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+use serenity::client::Context;
+use serenity::framework::standard::{Args, CommandResult};
+use serenity::model::channel::Message;
+use serenity::model::id::UserId;
+use sha2::{Digest, Sha256};
+
+/// The name of the attachment holding a fair roll's banner
+const BANNER_FILE: &str = "banner.json";
+
+/// The parts of a banner a fair roll depends on, with the pool already resolved. It is saved for
+/// every fair roll, so the roll can still be verified after the banner is edited or removed
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairBanner {
+    pub rates: Rates,
+    pub pool: Pool,
+    pub priority: Vec<PriorityInfo>,
+}
+
+impl FairBanner {
+    pub fn of(info: &BannerInfo) -> Self {
+        Self {
+            rates: info.rates(),
+            pool: info.pool.clone(),
+            priority: info.priority.clone(),
+        }
+    }
+
+    /// The hex encoded `sha256` of the banner as JSON. It is shown with the roll, so that the
+    /// banner a roll is verified against can't be swapped out afterwards
+    pub fn hash(&self) -> String {
+        to_hex(&Sha256::digest(self.to_json().as_bytes()))
+    }
+
+    /// The banner as it is attached to fair rolls and their verification, as [`BANNER_FILE`]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    fn student_rate(&self, name: &str, rates: Rates) -> Option<f32> {
+        self.pool
+            .student_rate(&self.priority, self.rates, name, rates)
+    }
+}
+
+/// Creates a new hex encoded server seed
+pub fn new_seed() -> String {
+    to_hex(&rand::random::<[u8; 32]>())
+}
+
+/// The hash published before a seed is used, i.e. `sha256(seed)`
+pub fn commitment(seed: &str) -> String {
+    to_hex(&Sha256::digest(seed.as_bytes()))
+}
+
+/// Rolls `count` times on a banner, deterministically.
+///
+/// The RNG is ChaCha20 seeded with `sha256("<seed>:<nonce>")`. Every pull draws a number in
+/// `[0, 100)` to pick the rarity, then a number in `[0, rarity rate)` which is matched against
+/// the cumulative rates of that rarity's pool, in the order the banner lists them.
+/// The 10th pull of every 10 uses the guaranteed rates, like a 10-roll does.
+pub fn roll(banner: &FairBanner, seed: &str, nonce: u64, count: u32) -> Vec<String> {
+    let mut rng_seed = [0; 32];
+    rng_seed.copy_from_slice(&Sha256::digest(format!("{}:{}", seed, nonce).as_bytes()));
+    let mut rng = ChaCha20Rng::from_seed(rng_seed);
+
+    (0..count)
+        .filter_map(|i| {
+            let rates = if count >= 10 && i % 10 == 9 {
+                banner.rates.guaranteed()
+            } else {
+                banner.rates
+            };
+
+            let x = rng.gen::<f32>() * 100.0;
+            let rarity = if x < rates.three {
+                Rarity::Three
+            } else if x < rates.three + rates.two {
+                Rarity::Two
+            } else {
+                Rarity::One
+            };
+
+            let pool = banner.pool.of(rarity);
+            let y = rng.gen::<f32>() * rates.of(rarity);
+            let mut total = 0.0;
+            for name in pool.iter() {
+                total += banner.student_rate(name, rates).unwrap_or(0.0);
+                if y < total {
+                    return Some(name.clone());
+                }
+            }
+
+            // Rounding can leave `y` just past the last student
+            pool.last().cloned()
+        })
+        .collect()
+}
+
+pub async fn fair_seed(ctx: &Context, msg: &Message) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+    info!("{} requested their fair roll commitment", author_name);
+
+    let seed = pending_seed(msg.author.id);
+
+    msg.reply(
+        ctx,
+        format!(
+            "The commitment of your next fair roll is `{}`. Its seed will be revealed after the roll",
+            commitment(&seed)
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn fair_roll(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let count = match parse_count(&mut args) {
+        Some(10) => 10,
+        Some(1) | None => 1,
+        Some(_) => {
+            msg.reply(ctx, "Fair rolls can only be single rolls or 10-rolls")
+                .await?;
+            return Ok(());
+        }
+    };
+    info!("{} requested a fair {}-roll", author_name, count);
+
+    let entry = match select_banner(ctx, msg, args.rest()).await? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+
+    // The seed's hash has to be public before the roll, or it proves nothing
+    if store::user(msg.author.id).fair_seed.is_none() {
+        let seed = pending_seed(msg.author.id);
+        msg.reply(
+            ctx,
+            format!(
+                "Your first fair roll will use the seed with the hash `{}`. Use the command again to roll",
+                commitment(&seed)
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let cost = if count == 10 { ROLL10_COST } else { ROLL_COST };
+    let charge = match pay(ctx, msg, cost).await? {
+        Some(charge) => charge,
+        None => return Ok(()),
+    };
+
+    let nonce = msg.id.0;
+    let definition = FairBanner::of(&entry.info);
+    let banner_hash = definition.hash();
+    let (fair_roll, students, points, next_seed) = store::update(|store| {
+        let now = Utc::now();
+        let user = store.users.entry(msg.author.id.0).or_default();
+
+        let next_seed = new_seed();
+        let seed = user
+            .fair_seed
+            .replace(next_seed.clone())
+            .unwrap_or_default();
+
+        let results = roll(&definition, &seed, nonce, count);
+        let students: Vec<Student> = results
+            .iter()
+            .filter_map(|name| find_student(name))
+            .collect();
+
+        for student in students.iter() {
+            recruit(user, student, now);
+        }
+        record_roll(user, &entry, &students, msg.guild_id, now);
+        let points = user.add_points(&entry.info.id, count);
+
+        store
+            .fair_banners
+            .entry(banner_hash.clone())
+            .or_insert_with(|| definition.clone());
+        let fair_roll = FairRoll {
+            id: store.fair_rolls.len() as u64 + 1,
+            user: msg.author.id.0,
+            banner: entry.info.id.clone(),
+            count,
+            seed,
+            nonce,
+            time: now,
+            results,
+            banner_hash: Some(banner_hash.clone()),
+        };
+        store.fair_rolls.push(fair_roll.clone());
+
+        (fair_roll, students, points, next_seed)
+    });
+
+    let language = preferred_language(msg);
+    let pulls = students
+        .iter()
+        .map(|student| {
+            format!(
+                "{} {}",
+                display_name(&student.name, language),
+                get_rarity_stars(student.rarity)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let max_rarity = students
+        .iter()
+        .map(|student| student.rarity)
+        .max()
+        .unwrap_or(Rarity::One);
+
+    let json = definition.to_json();
+    let files = vec![(json.as_bytes(), BANNER_FILE)];
+    msg.channel_id
+        .send_files(ctx, files, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!(
+                        "Fair {} {}-roll #{}",
+                        translate(&entry.banner.name, language),
+                        count,
+                        fair_roll.id
+                    ))
+                    .description(pulls)
+                    .field("Seed", format!("`{}`", fair_roll.seed), false)
+                    .field(
+                        "Commitment",
+                        format!("`{}`", commitment(&fair_roll.seed)),
+                        false,
+                    )
+                    .field("Nonce", fair_roll.nonce, true)
+                    .field("Banner Hash", format!("`{}`", banner_hash), false)
+                    .field("Recruitment Points", format_points(points), true)
+                    .field(
+                        "Next Commitment",
+                        format!("`{}`", commitment(&next_seed)),
+                        false,
+                    )
+                    .footer(|footer| footer.text(format!("!verify {}", fair_roll.id)))
+                    .colour(get_rarity_colour(max_rarity));
+
+                if let Charge::Paid(pyroxene) = charge {
+                    embed.field("Pyroxene", pyroxene, true);
+                }
+
+                embed
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn verify(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let id = match args.single::<u64>() {
+        Ok(id) => id,
+        Err(_) => {
+            msg.reply(ctx, "Usage: `!verify <roll id>`").await?;
+            return Ok(());
+        }
+    };
+    info!("{} requested to verify fair roll #{}", author_name, id);
+
+    let (fair_roll, recorded_banner) = match store::read(|store| {
+        let fair_roll = store
+            .fair_rolls
+            .iter()
+            .find(|fair_roll| fair_roll.id == id)?;
+        let recorded_banner = fair_roll
+            .banner_hash
+            .as_ref()
+            .and_then(|hash| store.fair_banners.get(hash))
+            .cloned();
+        Some((fair_roll.clone(), recorded_banner))
+    }) {
+        Some(found) => found,
+        None => {
+            msg.reply(ctx, format!("There is no fair roll #{}", id))
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let entry = registry().get(&fair_roll.banner).cloned();
+
+    // Rolls saved before banners were recorded with them can only be checked against the banner
+    // as it is now
+    let definition = match (&recorded_banner, &entry) {
+        (Some(definition), _) => definition.clone(),
+        (None, Some(entry)) => FairBanner::of(&entry.info),
+        (None, None) => {
+            msg.reply(
+                ctx,
+                format!(
+                    "The banner of fair roll #{} ({}) no longer exists, so it can't be recomputed",
+                    id, fair_roll.banner
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let recomputed = roll(
+        &definition,
+        &fair_roll.seed,
+        fair_roll.nonce,
+        fair_roll.count,
+    );
+    let verdict = match (recomputed == fair_roll.results, &recorded_banner) {
+        (true, Some(_)) => "The results match the seed and the recorded banner",
+        (false, Some(_)) => "The results do NOT match the seed and the recorded banner",
+        (true, None) => "The results match the seed and the banner's current definition",
+        (false, None) => "The results do NOT match the seed and the banner's current definition",
+    };
+    let language = preferred_language(msg);
+    let banner_name = match &entry {
+        Some(entry) => translate(&entry.banner.name, language),
+        None => fair_roll.banner.clone(),
+    };
+    let recorded = display_names(fair_roll.results.iter(), language);
+    let recomputed_names = display_names(recomputed.iter(), language);
+
+    let json = definition.to_json();
+    let files = vec![(json.as_bytes(), BANNER_FILE)];
+    msg.channel_id
+        .send_files(ctx, files, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!("Fair Roll #{}", fair_roll.id))
+                    .description(verdict)
+                    .field("Rolled By", format!("<@{}>", fair_roll.user), true)
+                    .field("Banner", banner_name, true)
+                    .field("Time", fair_roll.time.format("%Y-%m-%d %H:%M UTC"), true)
+                    .field("Seed", format!("`{}`", fair_roll.seed), false)
+                    .field(
+                        "Commitment",
+                        format!("`{}`", commitment(&fair_roll.seed)),
+                        false,
+                    )
+                    .field("Nonce", fair_roll.nonce, true)
+                    .field("Banner Hash", format!("`{}`", definition.hash()), false)
+                    .field("Recorded", recorded.join(", "), false)
+                    .field("Recomputed", recomputed_names.join(", "), false)
+                    .colour(BLUE_ARCHIVE_BLUE)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Returns the seed of a user's next fair roll, creating it if there isn't one yet
+fn pending_seed(id: UserId) -> String {
+    store::update_user(id, |user| {
+        user.fair_seed.get_or_insert_with(new_seed).clone()
+    })
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::Store;
+
+    fn banner() -> FairBanner {
+        serde_json::from_str(
+            r#"{
+                "rates": { "one": 79.0, "two": 18.5, "three": 2.5 },
+                "pool": {
+                    "standard": true,
+                    "three": ["ホシノ", "シロコ", "ヒナ"],
+                    "two": ["アカリ", "ジュンコ"],
+                    "one": ["チナツ", "ハルカ"]
+                },
+                "priority": [{ "name": "ホシノ", "rate": 0.7 }]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn commitment_is_the_sha256_of_the_seed() {
+        assert_eq!(
+            commitment(""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert_eq!(
+            commitment("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn new_seeds_are_random() {
+        assert_eq!(new_seed().len(), 64);
+        assert_ne!(new_seed(), new_seed());
+    }
+
+    const SEED: &str = "00112233445566778899aabbccddeeff00112233445566778899aabbccddeeff";
+
+    #[test]
+    fn rolls_are_deterministic() {
+        let expected = [
+            "ハルカ",
+            "ハルカ",
+            "チナツ",
+            "ハルカ",
+            "ホシノ",
+            "チナツ",
+            "ハルカ",
+            "チナツ",
+            "ハルカ",
+            "ジュンコ",
+        ];
+
+        assert_eq!(roll(&banner(), SEED, 42, 10), expected);
+        assert_eq!(roll(&banner(), SEED, 42, 10), expected);
+        assert_ne!(roll(&banner(), SEED, 43, 10), expected);
+    }
+
+    #[test]
+    fn the_last_pull_of_a_10_roll_is_at_least_2_stars() {
+        for nonce in 0..100 {
+            let results = roll(&banner(), SEED, nonce, 10);
+            assert!(!["チナツ", "ハルカ"].contains(&results[9].as_str()));
+        }
+    }
+
+    #[test]
+    fn recorded_rolls_verify() {
+        let banner = banner();
+        let fair_roll = FairRoll {
+            id: 1,
+            user: 1,
+            banner: "test".to_string(),
+            count: 10,
+            seed: SEED.to_string(),
+            nonce: 42,
+            time: Utc::now(),
+            results: roll(&banner, SEED, 42, 10),
+            banner_hash: Some(banner.hash()),
+        };
+        let mut store = Store::default();
+        store.fair_banners.insert(banner.hash(), banner.clone());
+        store.fair_rolls.push(fair_roll);
+
+        let json = serde_json::to_string(&store).unwrap();
+        let saved: Store = serde_json::from_str(&json).unwrap();
+        let saved_roll = &saved.fair_rolls[0];
+        let hash = saved_roll.banner_hash.as_ref().unwrap();
+        let definition = &saved.fair_banners[hash];
+
+        assert_eq!(&definition.hash(), hash);
+        assert_eq!(
+            roll(
+                definition,
+                &saved_roll.seed,
+                saved_roll.nonce,
+                saved_roll.count
+            ),
+            saved_roll.results
+        );
+    }
+
+    #[test]
+    fn the_banner_hash_is_the_sha256_of_the_published_banner() {
+        let banner = banner();
+        let published: FairBanner = serde_json::from_str(&banner.to_json()).unwrap();
+
+        assert_eq!(published.to_json(), banner.to_json());
+        assert_eq!(published.hash(), banner.hash());
+    }
+
+    #[test]
+    fn editing_the_banner_changes_its_hash() {
+        let mut edited = banner();
+        edited.priority[0].rate = 1.4;
+
+        assert_ne!(edited.hash(), banner().hash());
+    }
+}
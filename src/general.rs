@@ -1,6 +1,9 @@
+use crate::banner::{self, Region};
+use crate::language::{language_name, parse_language, preferred_language, DEFAULT_LANGUAGE};
+use crate::store;
 use log::{info, warn};
 use serenity::client::Context;
-use serenity::framework::standard::CommandResult;
+use serenity::framework::standard::{Args, CommandResult};
 use serenity::model::channel::Message;
 use serenity::utils::Colour;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -52,3 +55,173 @@ pub async fn source(ctx: &Context, msg: &Message) -> CommandResult {
 
     Ok(())
 }
+
+pub async fn reload(ctx: &Context, msg: &Message) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+    info!("{} requested a reload of the data directory", author_name);
+
+    match banner::reload() {
+        Ok((students, banners)) => {
+            msg.reply(
+                ctx,
+                format!("Reloaded {} students and {} banners", students, banners),
+            )
+            .await?;
+        }
+        Err(err) => {
+            warn!("Refused to reload the data directory: {}", err);
+            msg.reply(
+                ctx,
+                format!(
+                    "アロナ kept the current data, since the new data is invalid: {}",
+                    err
+                ),
+            )
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn region(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let region = match Region::parse(args.rest().trim()) {
+        Some(region) => region,
+        None => {
+            msg.reply(
+                ctx,
+                format!(
+                    "This server uses the {} banners. Use `!region jp` or `!region global`",
+                    store::guild(guild_id).region
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "{} set the region of {} to {}",
+        author_name, guild_id.0, region
+    );
+
+    store::update_guild(guild_id, |settings| settings.region = region);
+
+    msg.reply(
+        ctx,
+        format!(
+            "This server now uses the {} banners. Commands can still pick the other region, e.g. `!roll {}`",
+            region,
+            match region {
+                Region::Japan => "global",
+                Region::Global => "jp",
+            }
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn language(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+    let word = args.rest().trim();
+
+    if word.eq_ignore_ascii_case("reset") {
+        info!("{} reset their language", author_name);
+        store::update_user(msg.author.id, |user| user.language = None);
+
+        msg.reply(
+            ctx,
+            format!(
+                "アロナ will use this server's language, {}",
+                language_name(preferred_language(msg))
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let language = match parse_language(word) {
+        Some(language) => language,
+        None => {
+            msg.reply(
+                ctx,
+                format!(
+                    "アロナ shows names in {} for you. Use `!language en`, `!language jp` or `!language reset`",
+                    language_name(preferred_language(msg))
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "{} set their language to {}",
+        author_name,
+        language_name(language)
+    );
+
+    store::update_user(msg.author.id, |user| user.language = Some(language));
+
+    msg.reply(
+        ctx,
+        format!(
+            "アロナ will show names in {} for you",
+            language_name(language)
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+pub async fn server_language(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let language = match parse_language(args.rest().trim()) {
+        Some(language) => language,
+        None => {
+            let current = store::guild(guild_id).language.unwrap_or(DEFAULT_LANGUAGE);
+            msg.reply(
+                ctx,
+                format!(
+                    "This server shows names in {}. Use `!serverlanguage en` or `!serverlanguage jp`",
+                    language_name(current)
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "{} set the language of {} to {}",
+        author_name,
+        guild_id.0,
+        language_name(language)
+    );
+
+    store::update_guild(guild_id, |settings| settings.language = Some(language));
+
+    msg.reply(
+        ctx,
+        format!(
+            "This server now shows names in {}. Users can still pick their own with `!language`",
+            language_name(language)
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
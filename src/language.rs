@@ -0,0 +1,76 @@
+use crate::store;
+use blue_gacha::i18n::{I18nString, Language};
+use serenity::model::channel::Message;
+
+/// Every language names are translated to, in the order they're fallen back on
+pub const LANGUAGES: [Language; 2] = [Language::Japanese, Language::English];
+/// The language used when neither the user nor their server picked one
+pub const DEFAULT_LANGUAGE: Language = Language::Japanese;
+
+/// Parses a language as it is written in commands, e.g. `en` or `jp`
+pub fn parse_language(word: &str) -> Option<Language> {
+    match word.to_lowercase().as_str() {
+        "en" | "eng" | "english" => Some(Language::English),
+        "jp" | "ja" | "jpn" | "japanese" | "日本語" => Some(Language::Japanese),
+        _ => None,
+    }
+}
+
+pub fn language_name(language: Language) -> &'static str {
+    match language {
+        Language::English => "English",
+        Language::Japanese => "日本語",
+    }
+}
+
+/// The language to answer a message in: its author's choice, then their server's,
+/// then [`DEFAULT_LANGUAGE`]
+pub fn preferred_language(msg: &Message) -> Language {
+    store::read(|store| {
+        let user = store
+            .users
+            .get(&msg.author.id.0)
+            .and_then(|user| user.language);
+        let guild = msg
+            .guild_id
+            .and_then(|id| store.guilds.get(&id.0))
+            .and_then(|settings| settings.language);
+
+        user.or(guild)
+    })
+    .unwrap_or(DEFAULT_LANGUAGE)
+}
+
+/// The translation of a name in `language`, falling back on the other [`LANGUAGES`] in order
+/// when it hasn't been translated to `language`
+pub fn translate(name: &I18nString, language: Language) -> String {
+    std::iter::once(language)
+        .chain(LANGUAGES.iter().copied())
+        .find_map(|language| get(name, language))
+        .unwrap_or_default()
+}
+
+/// The translation shown next to [`translate`]'s, i.e. the first other one there is
+pub fn secondary(name: &I18nString, language: Language) -> Option<String> {
+    let primary = translate(name, language);
+
+    LANGUAGES
+        .iter()
+        .filter_map(|&language| get(name, language))
+        .find(|translation| *translation != primary)
+}
+
+/// Formats a name with its secondary translation, e.g. `ミドリ (Midori)` or `Midori (ミドリ)`
+pub fn display_name(name: &I18nString, language: Language) -> String {
+    let primary = translate(name, language);
+
+    match secondary(name, language) {
+        Some(secondary) => format!("{} ({})", primary, secondary),
+        None => primary,
+    }
+}
+
+fn get(name: &I18nString, language: Language) -> Option<String> {
+    name.get(language)
+        .filter(|translation| !translation.trim().is_empty())
+}
@@ -1,3 +1,15 @@
+pub mod banner;
+pub mod check;
+pub mod collection;
+pub mod economy;
+pub mod fair;
 pub mod general;
 pub mod image;
+pub mod language;
+pub mod lookup;
+pub mod odds;
 pub mod recruitment;
+pub mod simulator;
+pub mod stats;
+pub mod store;
+pub mod student;
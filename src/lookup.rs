@@ -0,0 +1,488 @@
+use crate::banner::{all_nicknames, all_students, Nicknames, StudentInfo};
+use crate::language::{display_name, preferred_language};
+use blue_gacha::i18n::Language;
+use serenity::client::Context;
+use serenity::model::channel::Message;
+
+const MAX_SUGGESTIONS: usize = 5;
+/// Prefixes shorter than this are too vague to pick a student by
+const MIN_PREFIX_LEN: usize = 3;
+
+/// The result of looking up a student by name
+pub enum Lookup {
+    Found(Box<StudentInfo>),
+    /// Nothing matched well enough to be sure, but these students are close
+    Suggestions(Vec<StudentInfo>),
+    NotFound,
+}
+
+/// Looks up a student in students.json by their Japanese, English or romanised name,
+/// or by any of their nicknames. Kana can be written in hiragana or katakana, and small typos are
+/// tolerated as long as only one student is close to the query
+pub fn lookup(query: &str) -> Lookup {
+    lookup_in(&all_students(), &all_nicknames(), query)
+}
+
+/// Looks up a student in `students`. See [`lookup`]
+pub fn lookup_in(students: &[StudentInfo], nicknames: &Nicknames, query: &str) -> Lookup {
+    let query = query.trim();
+    if let Some(info) = students.iter().find(|info| info.student.name == query) {
+        return Lookup::Found(Box::new(info.clone()));
+    }
+
+    let key = normalize(query);
+    let key_len = key.chars().count();
+    if key_len == 0 {
+        return Lookup::NotFound;
+    }
+
+    // Every student with the distance of their closest name to the query
+    let mut candidates: Vec<(usize, bool, &StudentInfo)> = students
+        .iter()
+        .map(|info| {
+            let names: Vec<String> = names(info, nicknames)
+                .map(|name| normalize(&name))
+                .collect();
+            let distance = names
+                .iter()
+                .map(|name| distance(&key, name))
+                .min()
+                .unwrap_or(usize::MAX);
+            let prefix =
+                key_len >= MIN_PREFIX_LEN && names.iter().any(|name| name.starts_with(&key));
+
+            (distance, prefix, info)
+        })
+        .collect();
+    candidates.sort_by_key(|&(distance, prefix, _)| (distance, !prefix));
+
+    let exact: Vec<&StudentInfo> = candidates
+        .iter()
+        .filter(|&&(distance, _, _)| distance == 0)
+        .map(|&(_, _, info)| info)
+        .collect();
+    match exact.len() {
+        0 => {}
+        1 => return Lookup::Found(Box::new(exact[0].clone())),
+        _ => return Lookup::Suggestions(exact.into_iter().cloned().collect()),
+    }
+
+    let prefixed: Vec<&StudentInfo> = candidates
+        .iter()
+        .filter(|&&(_, prefix, _)| prefix)
+        .map(|&(_, _, info)| info)
+        .collect();
+    if prefixed.len() == 1 {
+        return Lookup::Found(Box::new(prefixed[0].clone()));
+    }
+
+    // A typo every 4 letters is forgiven if no other student is as close
+    if let Some(&(best, _, info)) = candidates.first() {
+        let runner_up = candidates.get(1).map(|&(distance, _, _)| distance);
+        if best <= key_len / 4 && runner_up.map(|distance| distance > best).unwrap_or(true) {
+            return Lookup::Found(Box::new(info.clone()));
+        }
+    }
+
+    // Names starting with the query are more likely to be what was meant than similar ones
+    candidates.sort_by_key(|&(_, prefix, _)| !prefix);
+    let suggestions: Vec<StudentInfo> = candidates
+        .iter()
+        .filter(|&&(distance, prefix, _)| prefix || distance <= (key_len / 2).max(1))
+        .take(MAX_SUGGESTIONS)
+        .map(|&(_, _, info)| info.clone())
+        .collect();
+
+    if suggestions.is_empty() {
+        Lookup::NotFound
+    } else {
+        Lookup::Suggestions(suggestions)
+    }
+}
+
+/// Looks up the student a command was given, replying with what went wrong if there's no single
+/// match
+pub(crate) async fn select_student(
+    ctx: &Context,
+    msg: &Message,
+    query: &str,
+) -> serenity::Result<Option<StudentInfo>> {
+    let query = query.trim();
+
+    let reply = match lookup(query) {
+        Lookup::Found(info) => return Ok(Some(*info)),
+        Lookup::Suggestions(suggestions) => {
+            let language = preferred_language(msg);
+            format!(
+                "アロナ doesn't know a student called \"{}\". Did you mean {}?",
+                query,
+                suggestions
+                    .iter()
+                    .map(|info| display_name(&info.student.name, language))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )
+        }
+        Lookup::NotFound => format!("アロナ doesn't know a student called \"{}\"", query),
+    };
+
+    msg.reply(ctx, reply).await?;
+    Ok(None)
+}
+
+/// Every name a student can be looked up by
+fn names<'a>(info: &'a StudentInfo, nicknames: &'a Nicknames) -> impl Iterator<Item = String> + 'a {
+    let jpn = info.student.name.to_string();
+    let nicknames = nicknames.get(&jpn).into_iter().flatten().cloned();
+
+    std::iter::once(jpn)
+        .chain(info.student.name.get(Language::English))
+        .chain(nicknames)
+}
+
+/// Reduces a name to a form in which different spellings of it compare equal, e.g.
+/// `ユウカ`, `ゆうか`, `Yuuka` and `yuka` all become `yuka`
+pub fn normalize(name: &str) -> String {
+    const SPELLINGS: [(&str, &str); 6] = [
+        ("tsu", "tu"),
+        ("shi", "si"),
+        ("chi", "ti"),
+        ("fu", "hu"),
+        ("ji", "zi"),
+        ("ou", "o"),
+    ];
+    const PARTIAL_SPELLINGS: [(&str, &str); 3] = [("ts", "t"), ("sh", "s"), ("ch", "t")];
+
+    let mut name: String = romaji(&name.to_lowercase())
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+    for (from, to) in SPELLINGS.iter() {
+        name = name.replace(from, to);
+    }
+
+    // A name can be cut off partway through a syllable, e.g. the `mash` of `mashiro`
+    for (from, to) in PARTIAL_SPELLINGS.iter() {
+        if name.ends_with(from) {
+            name.truncate(name.len() - from.len());
+            name.push_str(to);
+            break;
+        }
+    }
+
+    // Long vowels are written both ways
+    let mut normalized = String::with_capacity(name.len());
+    for c in name.chars() {
+        if !(is_vowel(c) && normalized.ends_with(c)) {
+            normalized.push(c);
+        }
+    }
+
+    normalized
+}
+
+/// Transliterates kana to Hepburn romaji, leaving everything else as it is
+pub fn romaji(text: &str) -> String {
+    let mut romaji = String::with_capacity(text.len() * 2);
+    let mut double = false;
+
+    for c in text.chars().map(to_katakana) {
+        match c {
+            'ッ' => double = true,
+            'ー' => {
+                if let Some(vowel) = romaji.chars().last().filter(|&c| is_vowel(c)) {
+                    romaji.push(vowel);
+                }
+            }
+            'ャ' | 'ュ' | 'ョ' => {
+                if romaji.ends_with('i') {
+                    romaji.pop();
+                }
+                if !(romaji.ends_with("sh") || romaji.ends_with("ch") || romaji.ends_with('j')) {
+                    romaji.push('y');
+                }
+                romaji.push(small_vowel(c));
+            }
+            'ァ' | 'ィ' | 'ゥ' | 'ェ' | 'ォ' => {
+                if romaji.ends_with(is_vowel) {
+                    romaji.pop();
+                }
+                romaji.push(small_vowel(c));
+            }
+            _ => match syllable(c) {
+                Some(syllable) => {
+                    if double {
+                        romaji.extend(syllable.chars().next().filter(|&c| !is_vowel(c)));
+                    }
+                    romaji.push_str(syllable);
+                    double = false;
+                }
+                None => romaji.push(c),
+            },
+        }
+    }
+
+    romaji
+}
+
+/// The Levenshtein distance between two strings, counted in characters
+pub fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, &b) in b.iter().enumerate() {
+            let substitution = previous[j] + if a == b { 0 } else { 1 };
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+fn to_katakana(c: char) -> char {
+    match c {
+        'ぁ'..='ゖ' => std::char::from_u32(c as u32 + 0x60).unwrap_or(c),
+        _ => c,
+    }
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'i' | 'u' | 'e' | 'o')
+}
+
+fn small_vowel(c: char) -> char {
+    match c {
+        'ァ' => 'a',
+        'ィ' => 'i',
+        'ゥ' | 'ュ' => 'u',
+        'ェ' => 'e',
+        'ォ' | 'ョ' => 'o',
+        _ => 'a',
+    }
+}
+
+fn syllable(c: char) -> Option<&'static str> {
+    let syllable = match c {
+        'ア' => "a",
+        'イ' => "i",
+        'ウ' => "u",
+        'エ' => "e",
+        'オ' => "o",
+        'カ' => "ka",
+        'キ' => "ki",
+        'ク' => "ku",
+        'ケ' => "ke",
+        'コ' => "ko",
+        'ガ' => "ga",
+        'ギ' => "gi",
+        'グ' => "gu",
+        'ゲ' => "ge",
+        'ゴ' => "go",
+        'サ' => "sa",
+        'シ' => "shi",
+        'ス' => "su",
+        'セ' => "se",
+        'ソ' => "so",
+        'ザ' => "za",
+        'ジ' | 'ヂ' => "ji",
+        'ズ' | 'ヅ' => "zu",
+        'ゼ' => "ze",
+        'ゾ' => "zo",
+        'タ' => "ta",
+        'チ' => "chi",
+        'ツ' => "tsu",
+        'テ' => "te",
+        'ト' => "to",
+        'ダ' => "da",
+        'デ' => "de",
+        'ド' => "do",
+        'ナ' => "na",
+        'ニ' => "ni",
+        'ヌ' => "nu",
+        'ネ' => "ne",
+        'ノ' => "no",
+        'ハ' => "ha",
+        'ヒ' => "hi",
+        'フ' => "fu",
+        'ヘ' => "he",
+        'ホ' => "ho",
+        'バ' => "ba",
+        'ビ' => "bi",
+        'ブ' => "bu",
+        'ベ' => "be",
+        'ボ' => "bo",
+        'パ' => "pa",
+        'ピ' => "pi",
+        'プ' => "pu",
+        'ペ' => "pe",
+        'ポ' => "po",
+        'マ' => "ma",
+        'ミ' => "mi",
+        'ム' => "mu",
+        'メ' => "me",
+        'モ' => "mo",
+        'ヤ' => "ya",
+        'ユ' => "yu",
+        'ヨ' => "yo",
+        'ラ' => "ra",
+        'リ' => "ri",
+        'ル' => "ru",
+        'レ' => "re",
+        'ロ' => "ro",
+        'ワ' => "wa",
+        'ヲ' => "wo",
+        'ン' => "n",
+        'ヴ' => "vu",
+        _ => return None,
+    };
+
+    Some(syllable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn students() -> Vec<StudentInfo> {
+        let names = [
+            ("シロコ", "Shiroko"),
+            ("マシロ", "Mashiro"),
+            ("ミドリ", "Midori"),
+            ("モモイ", "Momoi"),
+            ("アル", "Aru"),
+            ("アイリ", "Airi"),
+            ("アリス", "Arisu"),
+        ];
+
+        names
+            .iter()
+            .map(|(jpn, eng)| {
+                let json = format!(
+                    r#"{{ "name": {{ "translations": {{ "jpn": "{}", "eng": "{}" }} }}, "rarity": 3 }}"#,
+                    jpn, eng
+                );
+                serde_json::from_str(&json).unwrap()
+            })
+            .collect()
+    }
+
+    fn nicknames() -> Nicknames {
+        let mut nicknames = Nicknames::new();
+        nicknames.insert("アリス".to_string(), vec!["Alice".to_string()]);
+        nicknames
+    }
+
+    fn found(query: &str) -> Option<String> {
+        match lookup_in(&students(), &nicknames(), query) {
+            Lookup::Found(info) => Some(info.student.name.to_string()),
+            _ => None,
+        }
+    }
+
+    fn suggested(query: &str) -> Vec<String> {
+        match lookup_in(&students(), &nicknames(), query) {
+            Lookup::Suggestions(suggestions) => suggestions
+                .iter()
+                .map(|info| info.student.name.to_string())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn spellings_of_a_name_normalize_the_same() {
+        for name in ["ユウカ", "ゆうか", "ユーカ", "Yuuka", "yuka", "YUKA"].iter() {
+            assert_eq!(normalize(name), "yuka", "{}", name);
+        }
+
+        assert_eq!(normalize("Tsurugi"), normalize("ツルギ"));
+        assert_eq!(normalize("Chise"), normalize("ちせ"));
+        assert_eq!(normalize("Fuka"), normalize("フウカ"));
+    }
+
+    #[test]
+    fn partial_syllables_normalize_to_a_prefix() {
+        for (partial, name) in [("mash", "マシロ"), ("ts", "ツルギ"), ("ch", "チセ")].iter()
+        {
+            assert!(
+                normalize(name).starts_with(&normalize(partial)),
+                "{} should be a prefix of {}",
+                partial,
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn kana_is_transliterated_to_hepburn() {
+        assert_eq!(romaji("シロコ"), "shiroko");
+        assert_eq!(romaji("ジュンコ"), "junko");
+        assert_eq!(romaji("チセ"), "chise");
+        assert_eq!(romaji("ツバキ"), "tsubaki");
+        assert_eq!(romaji("フィーナ"), "fiina");
+        assert_eq!(romaji("ユーカ"), "yuuka");
+        assert_eq!(romaji("アッカ"), "akka");
+        assert_eq!(romaji("ひな"), "hina");
+        assert_eq!(romaji("Hina"), "Hina");
+    }
+
+    #[test]
+    fn distance_counts_edits() {
+        assert_eq!(distance("kitten", "sitting"), 3);
+        assert_eq!(distance("", "abc"), 3);
+        assert_eq!(distance("abc", ""), 3);
+        assert_eq!(distance("ヒナ", "ヒナ"), 0);
+        assert_eq!(distance("ヒナ", "ヒカ"), 1);
+    }
+
+    #[test]
+    fn names_are_found_in_any_script() {
+        assert_eq!(found("シロコ").as_deref(), Some("シロコ"));
+        assert_eq!(found("しろこ").as_deref(), Some("シロコ"));
+        assert_eq!(found("Shiroko").as_deref(), Some("シロコ"));
+        assert_eq!(found("  shiroko ").as_deref(), Some("シロコ"));
+    }
+
+    #[test]
+    fn nicknames_and_typos_are_found() {
+        assert_eq!(found("Alice").as_deref(), Some("アリス"));
+        assert_eq!(found("shirokp").as_deref(), Some("シロコ"));
+        assert_eq!(found("midoi").as_deref(), Some("ミドリ"));
+    }
+
+    #[test]
+    fn prefixes_are_found() {
+        assert_eq!(found("mash").as_deref(), Some("マシロ"));
+        assert_eq!(found("momo").as_deref(), Some("モモイ"));
+    }
+
+    #[test]
+    fn ambiguous_names_are_suggested() {
+        let suggestions = suggested("airu");
+        assert!(suggestions.contains(&"アル".to_string()));
+        assert!(suggestions.contains(&"アイリ".to_string()));
+    }
+
+    #[test]
+    fn unknown_names_are_not_found() {
+        assert!(matches!(
+            lookup_in(&students(), &nicknames(), "xyz"),
+            Lookup::NotFound
+        ));
+        assert!(matches!(
+            lookup_in(&students(), &nicknames(), ""),
+            Lookup::NotFound
+        ));
+    }
+
+    #[test]
+    fn nobody_is_found_among_no_students() {
+        assert!(matches!(
+            lookup_in(&[], &Nicknames::new(), "hina"),
+            Lookup::NotFound
+        ));
+    }
+}
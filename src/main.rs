@@ -6,19 +6,38 @@ use serenity::framework::standard::macros::{command, group, help};
 use serenity::framework::standard::{
     help_commands, Args, CommandGroup, CommandResult, HelpOptions, StandardFramework,
 };
+use serenity::http::Http;
 use serenity::model::channel::Message;
 use serenity::model::id::UserId;
 use std::collections::HashSet;
 use std::env;
+use std::process;
 
 #[group]
-#[commands(ping, source)]
+#[commands(ping, source, region, language, serverlanguage)]
 struct General;
 
 #[group]
-#[commands(roll, banner, roll10)]
+#[owners_only]
+#[commands(reload)]
+struct Owner;
+
+#[group]
+#[commands(roll, banner, roll10, spark, rolluntil, fairroll, fairseed, verify)]
 struct Recruitment;
 
+#[group]
+#[commands(collection, starup, student, students)]
+struct Collection;
+
+#[group]
+#[commands(balance, daily, grant, economy)]
+struct Economy;
+
+#[group]
+#[commands(stats, leaderboard, simulate, odds, rates)]
+struct Statistics;
+
 struct Handler;
 
 #[async_trait]
@@ -29,44 +48,114 @@ async fn main() {
     dotenv().ok();
     env_logger::init();
 
-    let framework = StandardFramework::new()
-        .configure(|c| c.prefix("!")) // set the bot's prefix to "!"
-        .group(&GENERAL_GROUP)
-        .group(&RECRUITMENT_GROUP)
-        .help(&MY_HELP);
-
-    debug!("Initialized the StandardFramework struct");
-
-    // Login with a bot token from the environment
-    let mut client = match env::var("DISCORD_DEV_BOT_TOKEN") {
+    let (token, bot_name) = match env::var("DISCORD_DEV_BOT_TOKEN") {
         Ok(token) => {
             debug!("DISCORD_ENV_BOT_TOKEN is present. Running as アロナDev");
-            let client = Client::builder(&token)
-                .event_handler(Handler)
-                .framework(framework)
-                .await
-                .expect("Failed to create Serenity Client");
-            info!("アロナDev Client has begun with Token: {}", &token);
-            client
+            (token, "アロナDev")
         }
         Err(_) => {
             debug!("DISCORD_ENV_BOT_TOKEN is not present. Running as アロナ");
             let token = env::var("DISCORD_BOT_TOKEN").expect("DISCORD_BOT_TOKEN was not set.");
-            let client = Client::builder(&token)
-                .event_handler(Handler)
-                .framework(framework)
-                .await
-                .expect("Failed to create Serenity Client");
-
-            info!("アロナ Client has begun with Token: {}", &token);
-            client
+            (token, "アロナ")
         }
     };
 
+    // Load everything up front, so a bad data or state file stops the bot here instead of
+    // failing inside the first command which needs it
+    if let Err(why) = arona::banner::init() {
+        error!("Failed to load the data directory: {}", why);
+        process::exit(1);
+    }
+    if let Err(why) = arona::store::init() {
+        error!("{}", why);
+        process::exit(1);
+    }
+
+    // Owner commands (e.g. !reload) are limited to whoever owns the bot's application
+    let mut owners = HashSet::new();
+    match Http::new_with_token(&token)
+        .get_current_application_info()
+        .await
+    {
+        Ok(info) => {
+            owners.insert(info.owner.id);
+            if let Some(team) = info.team {
+                owners.extend(team.members.iter().map(|member| member.user.id));
+            }
+        }
+        Err(why) => error!("Could not access the application info: {:?}", why),
+    }
+
+    let framework = StandardFramework::new()
+        .configure(|c| c.prefix("!").owners(owners)) // set the bot's prefix to "!"
+        .group(&GENERAL_GROUP)
+        .group(&OWNER_GROUP)
+        .group(&RECRUITMENT_GROUP)
+        .group(&COLLECTION_GROUP)
+        .group(&ECONOMY_GROUP)
+        .group(&STATISTICS_GROUP)
+        .help(&MY_HELP);
+
+    debug!("Initialized the StandardFramework struct");
+
+    // Login with a bot token from the environment
+    let mut client = Client::builder(&token)
+        .event_handler(Handler)
+        .framework(framework)
+        .await
+        .expect("Failed to create Serenity Client");
+    info!("{} Client has begun with Token: {}", bot_name, &token);
+
+    if env::var("ARONA_WATCH").is_ok() {
+        info!(
+            "Watching {} for changes",
+            arona::banner::data_dir().display()
+        );
+        tokio::spawn(arona::banner::watch());
+    }
+
+    tokio::spawn(arona::store::persist());
+
     // start listening for events by starting a single shard
-    if let Err(why) = client.start().await {
-        error!("An error occurred while running the client: {:?}", why);
+    tokio::select! {
+        result = client.start() => {
+            if let Err(why) = result {
+                error!("An error occurred while running the client: {:?}", why);
+            }
+        }
+        _ = tokio::signal::ctrl_c() => info!("Shutting down"),
     }
+
+    arona::store::flush().await;
+}
+
+#[command]
+async fn reload(ctx: &Context, msg: &Message) -> CommandResult {
+    arona::general::reload(ctx, msg).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("ADMINISTRATOR")]
+#[usage("[jp | global]")]
+async fn region(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::general::region(ctx, msg, args).await
+}
+
+#[command]
+#[aliases(lang)]
+#[usage("[en | jp | reset]")]
+async fn language(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::general::language(ctx, msg, args).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("ADMINISTRATOR")]
+#[aliases(guildlanguage)]
+#[usage("[en | jp]")]
+async fn serverlanguage(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::general::server_language(ctx, msg, args).await
 }
 
 #[command]
@@ -77,19 +166,147 @@ async fn ping(ctx: &Context, msg: &Message) -> CommandResult {
 
 #[command]
 #[aliases(pull)]
-async fn roll(ctx: &Context, msg: &Message) -> CommandResult {
-    arona::recruitment::roll(ctx, msg).await
+#[usage("[count] [image] [banner]")]
+async fn roll(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::recruitment::roll(ctx, msg, args).await
 }
 
 #[command]
 #[aliases(tenroll)]
-async fn roll10(ctx: &Context, msg: &Message) -> CommandResult {
-    arona::recruitment::roll10(ctx, msg).await
+#[usage("[banner]")]
+async fn roll10(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::recruitment::roll10(ctx, msg, args).await
+}
+
+#[command]
+#[usage("[list | history | banner] [jp | global]")]
+async fn banner(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::recruitment::banner(ctx, msg, args).await
+}
+
+#[command]
+#[aliases(until)]
+#[usage("<student> [banner]")]
+async fn rolluntil(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::recruitment::roll_until(ctx, msg, args).await
+}
+
+#[command]
+#[usage("<student> [banner]")]
+async fn spark(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::recruitment::spark(ctx, msg, args).await
+}
+
+#[command]
+#[aliases(fair)]
+#[usage("[10] [banner]")]
+async fn fairroll(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::fair::fair_roll(ctx, msg, args).await
+}
+
+#[command]
+#[aliases(commitment)]
+async fn fairseed(ctx: &Context, msg: &Message) -> CommandResult {
+    arona::fair::fair_seed(ctx, msg).await
+}
+
+#[command]
+#[usage("<roll id>")]
+async fn verify(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::fair::verify(ctx, msg, args).await
+}
+
+#[command]
+#[aliases(roster)]
+#[usage("[@user]")]
+async fn collection(ctx: &Context, msg: &Message) -> CommandResult {
+    arona::collection::collection(ctx, msg).await
+}
+
+#[command]
+#[aliases(profile, info)]
+#[usage("<student>")]
+async fn student(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::student::student(ctx, msg, args).await
+}
+
+#[command]
+#[aliases(search)]
+#[usage("[filter:value ...] [page]")]
+async fn students(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::student::students(ctx, msg, args).await
+}
+
+#[command]
+#[usage("<student>")]
+async fn starup(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::collection::starup(ctx, msg, args).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases(pyroxene, wallet)]
+#[usage("[@user]")]
+async fn balance(ctx: &Context, msg: &Message) -> CommandResult {
+    arona::economy::balance(ctx, msg).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[aliases(login)]
+async fn daily(ctx: &Context, msg: &Message) -> CommandResult {
+    arona::economy::daily(ctx, msg).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("ADMINISTRATOR")]
+#[usage("<@user> <amount>")]
+async fn grant(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::economy::grant(ctx, msg, args).await
+}
+
+#[command]
+#[only_in(guilds)]
+#[required_permissions("ADMINISTRATOR")]
+#[usage("[on | off]")]
+async fn economy(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::economy::economy(ctx, msg, args).await
+}
+
+#[command]
+#[aliases(luck)]
+#[usage("[@user] [banner]")]
+async fn stats(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::stats::stats(ctx, msg, args).await
+}
+
+#[command]
+#[aliases(lb, top)]
+#[only_in(guilds)]
+#[usage("[rate | rateup | spark] [all | banner | week] [jp | global]")]
+async fn leaderboard(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::stats::leaderboard(ctx, msg, args).await
+}
+
+#[command]
+#[aliases(sim)]
+#[usage("<student> [trials] [banner]")]
+async fn simulate(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::simulator::simulate(ctx, msg, args).await
+}
+
+#[command]
+#[aliases(chance)]
+#[usage("<student | 3*> <pulls> [banner]")]
+async fn odds(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::odds::odds(ctx, msg, args).await
 }
 
 #[command]
-async fn banner(ctx: &Context, msg: &Message) -> CommandResult {
-    arona::recruitment::banner(ctx, msg).await
+#[usage("[banner] [page]")]
+async fn rates(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    arona::odds::rates(ctx, msg, args).await
 }
 
 #[command]
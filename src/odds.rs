@@ -0,0 +1,291 @@
+use crate::banner::find_student;
+use crate::general::BLUE_ARCHIVE_BLUE;
+use crate::language::{display_name, preferred_language, translate};
+use crate::lookup::select_student;
+use crate::recruitment::{get_rarity_stars, read_student, select_banner, SPARK_COST};
+use blue_gacha::gacha::Rarity;
+use blue_gacha::i18n::Language;
+use log::info;
+use serenity::client::Context;
+use serenity::framework::standard::{Args, CommandResult};
+use serenity::model::channel::Message;
+
+const MAX_PULLS: u32 = 10000;
+
+/// The chance of pulling something at least once in `pulls` pulls done as 10-rolls.
+///
+/// `rate` is the chance (in percent) of a regular pull, while `guaranteed_rate` is the chance
+/// on the 10th pull of every 10-roll.
+pub fn chance_within(rate: f32, guaranteed_rate: f32, pulls: u32) -> f64 {
+    let guaranteed = pulls / 10;
+    let regular = pulls - guaranteed;
+
+    let miss = (1.0 - rate as f64 / 100.0).powi(regular as i32);
+    let guaranteed_miss = (1.0 - guaranteed_rate as f64 / 100.0).powi(guaranteed as i32);
+
+    1.0 - miss * guaranteed_miss
+}
+
+pub async fn odds(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let (query, pulls) = match (read_student(msg, &mut args, true), args.single::<u32>()) {
+        (Some(query), Ok(pulls)) => (query, pulls.min(MAX_PULLS)),
+        _ => {
+            msg.reply(ctx, "Usage: `!odds <student | 3*> <pulls> [banner]`")
+                .await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "{} requested the odds of {} in {} pulls",
+        author_name, query, pulls
+    );
+
+    let entry = match select_banner(ctx, msg, args.rest()).await? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+
+    let rates = entry.info.rates();
+    let language = preferred_language(msg);
+    let any_three_star = matches!(query.as_str(), "3*" | "3★" | "any");
+
+    let (target, rate, guaranteed_rate, sparkable) = if any_three_star {
+        (
+            "any 3★".to_string(),
+            rates.three,
+            rates.guaranteed().three,
+            false,
+        )
+    } else {
+        let student = match select_student(ctx, msg, &query).await? {
+            Some(info) => info.student,
+            None => return Ok(()),
+        };
+        let jpn = student.name.to_string();
+        let rate = entry.info.student_rate(&jpn, rates);
+        let guaranteed_rate = entry.info.student_rate(&jpn, rates.guaranteed());
+
+        match (rate, guaranteed_rate) {
+            (Some(rate), Some(guaranteed_rate)) => {
+                let sparkable = entry.info.sparkable.contains(&jpn);
+                (
+                    display_name(&student.name, language),
+                    rate,
+                    guaranteed_rate,
+                    sparkable,
+                )
+            }
+            _ => {
+                msg.reply(
+                    ctx,
+                    format!(
+                        "{} can't be pulled on {}",
+                        translate(&student.name, language),
+                        translate(&entry.banner.name, language)
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    };
+
+    let chance = if sparkable && pulls >= SPARK_COST {
+        1.0
+    } else {
+        chance_within(rate, guaranteed_rate, pulls)
+    };
+
+    let expected = (pulls - pulls / 10) as f64 * rate as f64 / 100.0
+        + (pulls / 10) as f64 * guaranteed_rate as f64 / 100.0;
+
+    let mut notes = vec![
+        "Pulls are assumed to be done as 10-rolls, with a 2★ or higher guaranteed on every 10th pull"
+            .to_string(),
+    ];
+    if sparkable {
+        notes.push(format!(
+            "Can be sparked after {} pulls, which guarantees them",
+            SPARK_COST
+        ));
+    }
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!("Odds of {} in {} pulls", target, pulls))
+                    .description(format!("On {}", translate(&entry.banner.name, language)))
+                    .field("Chance", format!("{:.2}%", chance * 100.0), true)
+                    .field("Rate per Pull", format!("{:.3}%", rate), true)
+                    .field("Expected Copies", format!("{:.2}", expected), true)
+                    .field("Notes", notes.join("\n"), false)
+                    .colour(BLUE_ARCHIVE_BLUE)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn rates(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    const STUDENTS_PER_PAGE: usize = 15;
+
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    // The page number is optional and comes after the banner
+    let mut words: Vec<&str> = args.rest().split_whitespace().collect();
+    let page = match words.last().and_then(|word| word.parse::<usize>().ok()) {
+        Some(page) => {
+            words.pop();
+            page.max(1)
+        }
+        None => 1,
+    };
+    info!("{} requested page {} of the rates", author_name, page);
+
+    let entry = match select_banner(ctx, msg, &words.join(" ")).await? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+    let info = &entry.info;
+    let rates = info.rates();
+    let language = preferred_language(msg);
+    let guaranteed = rates.guaranteed();
+
+    let rarity_rates = [Rarity::Three, Rarity::Two, Rarity::One]
+        .iter()
+        .map(|&rarity| {
+            format!(
+                "{} {}% ({}% on the 10th pull of a 10-roll)",
+                get_rarity_stars(rarity),
+                rates.of(rarity),
+                guaranteed.of(rarity)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let rate_ups = if info.priority.is_empty() {
+        "None".to_string()
+    } else {
+        info.priority
+            .iter()
+            .map(|priority| format!("{} {}%", name_of(&priority.name, language), priority.rate))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    let mut pages: Vec<Vec<(String, String)>> = vec![vec![
+        ("Rarity Rates".to_string(), rarity_rates),
+        ("Rate-up".to_string(), rate_ups),
+    ]];
+
+    for &rarity in [Rarity::Three, Rarity::Two, Rarity::One].iter() {
+        let lines: Vec<String> = info
+            .pool_of(rarity)
+            .iter()
+            .filter(|name| !info.priority.iter().any(|priority| priority.name == **name))
+            .filter_map(|name| {
+                info.student_rate(name, rates)
+                    .map(|rate| format!("{} {:.4}%", name_of(name, language), rate))
+            })
+            .collect();
+
+        let chunks = lines.chunks(STUDENTS_PER_PAGE).count();
+        for (i, chunk) in lines.chunks(STUDENTS_PER_PAGE).enumerate() {
+            let title = if chunks > 1 {
+                format!(
+                    "{} Students ({}/{})",
+                    get_rarity_stars(rarity),
+                    i + 1,
+                    chunks
+                )
+            } else {
+                format!("{} Students", get_rarity_stars(rarity))
+            };
+
+            pages.push(vec![(title, chunk.join("\n"))]);
+        }
+    }
+
+    let page_count = pages.len();
+    let page = page.min(page_count);
+    let fields: Vec<(String, String, bool)> = pages
+        .swap_remove(page - 1)
+        .into_iter()
+        .map(|(name, value)| (name, value, false))
+        .collect();
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!(
+                        "Rates of {}",
+                        translate(&entry.banner.name, language)
+                    ))
+                    .fields(fields)
+                    .footer(|footer| {
+                        footer.text(format!(
+                            "Page {}/{} • !rates {} <page>",
+                            page, page_count, info.id
+                        ))
+                    })
+                    .colour(BLUE_ARCHIVE_BLUE)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Formats the name of a student given by their Japanese name, e.g. `ミドリ (Midori)`
+fn name_of(jpn: &str, language: Language) -> String {
+    match find_student(jpn) {
+        Some(student) => display_name(&student.name, language),
+        None => jpn.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::chance_within;
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn no_pulls_never_succeed() {
+        assert_close(chance_within(2.5, 2.5, 0), 0.0);
+    }
+
+    #[test]
+    fn certain_pulls_always_succeed() {
+        assert_close(chance_within(100.0, 100.0, 1), 1.0);
+    }
+
+    #[test]
+    fn every_pull_is_an_independent_chance() {
+        assert_close(chance_within(2.5, 2.5, 10), 1.0 - 0.975f64.powi(10));
+        assert_close(chance_within(50.0, 50.0, 3), 0.875);
+    }
+
+    #[test]
+    fn the_tenth_pull_uses_the_guaranteed_rate() {
+        assert_close(chance_within(0.0, 100.0, 9), 0.0);
+        assert_close(chance_within(0.0, 100.0, 10), 1.0);
+        assert_close(
+            chance_within(18.5, 97.5, 20),
+            1.0 - 0.815f64.powi(18) * 0.025f64.powi(2),
+        );
+    }
+}
@@ -1,68 +1,111 @@
+use crate::banner::{format_duration, image_key, registry, BannerEntry, Region, DEFAULT_RATES};
+use crate::collection::recruit;
+use crate::economy::{self, Charge, ROLL10_COST, ROLL_COST};
 use crate::general::BLUE_ARCHIVE_BLUE;
 use crate::image::get_image_from_url;
-use blue_gacha::banner::{Banner, BannerBuilder};
+use crate::language::{display_name, preferred_language, secondary, translate};
+use crate::lookup::select_student;
+use crate::stats::record_roll;
+use crate::store;
+use blue_gacha::gacha::Rarity;
 use blue_gacha::gacha::Recruitment as RecruitmentTrait;
-use blue_gacha::gacha::{GachaBuilder, Rarity};
-use blue_gacha::i18n::Language;
 use blue_gacha::student::Student;
+use chrono::Utc;
 use image::jpeg::JpegEncoder;
 use image::{ColorType, ImageEncoder, RgbaImage};
-use lazy_static::lazy_static;
 use log::{error, info};
+use serenity::builder::CreateEmbed;
 use serenity::client::Context;
-use serenity::framework::standard::CommandResult;
+use serenity::framework::standard::{Args, CommandResult};
 use serenity::model::channel::Message;
 use serenity::utils::Colour;
 
+use std::sync::Arc;
 use std::time::Instant;
 
-const STUDENTS_JSON: &str = include_str!("../data/students.json");
-const CDN_URL: &str = "https://rerollcdn.com/BlueArchive";
-const BANNER_IMG_URL: &str =
-    "https://static.wikia.nocookie.net/blue-archive/images/7/70/Gacha_Banner_07.png";
+pub(crate) const CDN_URL: &str = "https://rerollcdn.com/BlueArchive";
 const THUMB_WIDTH: u32 = 202; // OG: 404 (2020-02-11) from https://thearchive.gg
 const THUMB_HEIGHT: u32 = 228; // OG: 456 (2020-02-11) from https://thearchive.gg
+pub(crate) const SPARK_COST: u32 = 200;
+const MAX_ROLLS: u32 = 200;
 
-lazy_static! {
-    static ref STUDENTS: Vec<Student> = serde_json::from_str(STUDENTS_JSON).unwrap();
-    static ref BANNER: Banner = create_2021_04_08_midori_banner();
-}
-
-pub async fn roll(ctx: &Context, msg: &Message) -> CommandResult {
+pub async fn roll(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    if let Some(count) = parse_count(&mut args) {
+        if count > MAX_ROLLS || count == 0 {
+            msg.reply(
+                ctx,
+                format!("アロナ can roll between 1 and {} times", MAX_ROLLS),
+            )
+            .await?;
+            return Ok(());
+        }
+
+        if count > 1 {
+            info!("{} requested {} rolls", author_name, count);
+            return roll_many(ctx, msg, count, args).await;
+        }
+    }
+
     info!("{} requested a single roll", author_name);
 
-    let channel = msg.channel_id;
-    let student = BANNER.roll();
+    let entry = match select_banner(ctx, msg, args.rest()).await? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
 
-    let eng_name = student.name.get(Language::English).unwrap();
-    let url_name = &eng_name;
+    let charge = match pay(ctx, msg, ROLL_COST).await? {
+        Some(charge) => charge,
+        None => return Ok(()),
+    };
 
-    let img_url = format!("{}/Characters/{}.png", CDN_URL, url_name);
-    let title_url = format!("https://www.thearchive.gg/characters/{}", url_name);
+    let channel = msg.channel_id;
+    let student = entry.banner.roll();
+    let points = store::update_user(msg.author.id, |user| {
+        let now = Utc::now();
+        recruit(user, &student, now);
+        record_roll(
+            user,
+            &entry,
+            std::slice::from_ref(&student),
+            msg.guild_id,
+            now,
+        );
+        user.add_points(&entry.info.id, 1)
+    });
+
+    let language = preferred_language(msg);
+    let secondary_name = secondary(&student.name, language).unwrap_or_default();
+
+    let img_url = portrait_url(&student);
+    let title_url = page_url(&student);
     let icon_url = format!("{}/Icons/icon-brand.png", CDN_URL);
     let rarity_colour = get_rarity_colour(student.rarity);
 
-    let rarity_str = match student.rarity {
-        Rarity::One => ":star:",
-        Rarity::Two => ":star::star:",
-        Rarity::Three => ":star::star::star:",
-    };
+    let rarity_str = get_rarity_stars(student.rarity);
 
     channel
         .send_message(ctx, |m| {
             m.embed(|embed| {
                 embed
                     .image(img_url)
-                    .title(format!("{}", student.name))
-                    .description(format!("{}\t{}", eng_name, rarity_str))
+                    .title(translate(&student.name, language))
+                    .description(format!("{}\t{}", secondary_name, rarity_str))
                     .url(title_url)
+                    .field("Recruitment Points", format_points(points), true)
                     .footer(|footer| {
                         footer
                             .icon_url(icon_url)
                             .text("Image Source: https://thearchive.gg")
                     })
-                    .colour(rarity_colour)
+                    .colour(rarity_colour);
+
+                if let Charge::Paid(pyroxene) = charge {
+                    embed.field("Pyroxene", pyroxene, true);
+                }
+
+                embed
             })
         })
         .await?;
@@ -70,9 +113,169 @@ pub async fn roll(ctx: &Context, msg: &Message) -> CommandResult {
     Ok(())
 }
 
-pub async fn roll10(ctx: &Context, msg: &Message) -> CommandResult {
+async fn roll_many(ctx: &Context, msg: &Message, count: u32, mut args: Args) -> CommandResult {
+    let show_image = matches!(args.current(), Some("image") | Some("img"));
+    if show_image {
+        args.advance();
+    }
+
+    let entry = match select_banner(ctx, msg, args.rest()).await? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+
+    let charge = match pay(ctx, msg, count * ROLL_COST).await? {
+        Some(charge) => charge,
+        None => return Ok(()),
+    };
+
+    let typing = msg.channel_id.start_typing(&ctx.http)?;
+
+    // Roll as many 10-rolls as possible so the 2★ guarantee still applies
+    let mut rolls: Vec<Vec<Student>> = Vec::new();
+    for _ in 0..count / 10 {
+        rolls.push(entry.banner.roll10().to_vec());
+    }
+    for _ in 0..count % 10 {
+        rolls.push(vec![entry.banner.roll()]);
+    }
+
+    let points = store::update_user(msg.author.id, |user| {
+        let now = Utc::now();
+        for students in rolls.iter() {
+            for student in students.iter() {
+                recruit(user, student, now);
+            }
+            record_roll(user, &entry, students, msg.guild_id, now);
+        }
+        user.add_points(&entry.info.id, count)
+    });
+
+    let pulled: Vec<Student> = rolls.into_iter().flatten().collect();
+    let count_of = |rarity: Rarity| {
+        pulled
+            .iter()
+            .filter(|student| student.rarity == rarity)
+            .count()
+    };
+    let notable: Vec<Student> = pulled
+        .iter()
+        .filter(|student| student.rarity == Rarity::Three || entry.info.is_rate_up(student))
+        .cloned()
+        .collect();
+
+    let language = preferred_language(msg);
+    let describe = |student: &Student| {
+        let name = display_name(&student.name, language);
+        if entry.info.is_rate_up(student) {
+            format!("{} - Rate-up", name)
+        } else {
+            name
+        }
+    };
+    let three_stars: Vec<String> = pulled
+        .iter()
+        .filter(|student| student.rarity == Rarity::Three)
+        .map(describe)
+        .collect();
+    let rate_ups: Vec<String> = pulled
+        .iter()
+        .filter(|student| student.rarity != Rarity::Three && entry.info.is_rate_up(student))
+        .map(describe)
+        .collect();
+
+    let max_rarity = pulled
+        .iter()
+        .map(|student| student.rarity)
+        .max()
+        .unwrap_or(Rarity::One);
+
+    let collage = if show_image && !notable.is_empty() {
+        match render_collage(&notable, 5).await {
+            Ok(jpeg) => Some(jpeg),
+            Err(err) => {
+                error!("Failed to Encode JPEG: {:?}", err);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    let title = format!("{} {}-roll", translate(&entry.banner.name, language), count);
+    let summary = format!(
+        "{} {}\n{} {}\n{} {}",
+        get_rarity_stars(Rarity::Three),
+        count_of(Rarity::Three),
+        get_rarity_stars(Rarity::Two),
+        count_of(Rarity::Two),
+        get_rarity_stars(Rarity::One),
+        count_of(Rarity::One)
+    );
+    let build_embed = |embed: &mut CreateEmbed| {
+        embed
+            .title(&title)
+            .description(&summary)
+            .field(
+                format!("3★ Students ({})", three_stars.len()),
+                list_or_none(&three_stars),
+                false,
+            )
+            .field(
+                format!("Other Rate-ups ({})", rate_ups.len()),
+                list_or_none(&rate_ups),
+                false,
+            )
+            .field("Recruitment Points", format_points(points), true)
+            .colour(get_rarity_colour(max_rarity));
+
+        if let Charge::Paid(pyroxene) = charge {
+            embed.field("Pyroxene", pyroxene, true);
+        }
+    };
+
+    let _ = typing.stop();
+    match &collage {
+        Some(jpeg) => {
+            let files = vec![(jpeg.as_slice(), "result.jpeg")];
+            msg.channel_id
+                .send_files(ctx, files, |m| {
+                    m.embed(|embed| {
+                        build_embed(embed);
+                        embed.attachment("result.jpeg")
+                    })
+                })
+                .await?;
+        }
+        None => {
+            msg.channel_id
+                .send_message(ctx, |m| {
+                    m.embed(|embed| {
+                        build_embed(embed);
+                        embed
+                    })
+                })
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn roll10(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
     info!("{} requested a ten roll", author_name);
+
+    let entry = match select_banner(ctx, msg, args.rest()).await? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+
+    let charge = match pay(ctx, msg, ROLL10_COST).await? {
+        Some(charge) => charge,
+        None => return Ok(()),
+    };
+
     let channel = msg.channel_id;
 
     let typing = channel.start_typing(&ctx.http)?;
@@ -83,17 +286,14 @@ pub async fn roll10(ctx: &Context, msg: &Message) -> CommandResult {
     let mut collage = RgbaImage::new(IMG_WIDTH, IMG_HEIGHT);
     let mut images: Vec<RgbaImage> = Vec::with_capacity(10);
 
-    let students = BANNER.roll10();
+    let students = entry.banner.roll10();
     let mut max_rarity = Rarity::One;
 
     let start = Instant::now();
     for student in students.iter() {
-        let eng_name = student.name.get(Language::English).unwrap();
-        let url_name = &eng_name;
-
         max_rarity = max_rarity.max(student.rarity);
 
-        let img_url = format!("{}/Characters/{}.png", CDN_URL, url_name);
+        let img_url = portrait_url(student);
         let image = get_image_from_url(&img_url, THUMB_WIDTH, THUMB_HEIGHT).await;
 
         images.push(image);
@@ -121,6 +321,7 @@ pub async fn roll10(ctx: &Context, msg: &Message) -> CommandResult {
 
     if let Err(err) = write_result {
         error!("Failed to Encode JPEG: {:?}", err);
+        economy::refund(msg, &charge, ROLL10_COST);
         msg.reply(
             ctx,
             "アロナ failed to perform your 10-roll. Please try again",
@@ -129,6 +330,16 @@ pub async fn roll10(ctx: &Context, msg: &Message) -> CommandResult {
         return Ok(());
     }
 
+    let points = store::update_user(msg.author.id, |user| {
+        let now = Utc::now();
+        for student in students.iter() {
+            recruit(user, student, now);
+        }
+        record_roll(user, &entry, &students, msg.guild_id, now);
+        user.add_points(&entry.info.id, 10)
+    });
+
+    let language = preferred_language(msg);
     let icon_url = format!("{}/Icons/icon-brand.png", CDN_URL);
 
     let files = vec![(jpeg.as_slice(), "result.jpeg")];
@@ -137,35 +348,397 @@ pub async fn roll10(ctx: &Context, msg: &Message) -> CommandResult {
         .send_files(ctx, files, |m| {
             m.embed(|embed| {
                 embed
-                    .title(format!("{} 10-roll", BANNER.name))
-                    .description(BANNER.name.get(Language::English).unwrap())
+                    .title(format!(
+                        "{} 10-roll",
+                        translate(&entry.banner.name, language)
+                    ))
+                    .description(secondary(&entry.banner.name, language).unwrap_or_default())
                     .attachment("result.jpeg")
+                    .field("Recruitment Points", format_points(points), true)
                     .colour(get_rarity_colour(max_rarity))
+                    .footer(|footer| {
+                        footer
+                            .icon_url(icon_url)
+                            .text("Image Source: https://thearchive.gg")
+                    });
+
+                if let Charge::Paid(pyroxene) = charge {
+                    embed.field("Pyroxene", pyroxene, true);
+                }
+
+                embed
+            })
+        })
+        .await?;
+    Ok(())
+}
+
+pub async fn spark(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let query = match read_student(msg, &mut args, false) {
+        Some(query) => query,
+        None => {
+            msg.reply(ctx, "Tell アロナ which student you'd like to spark")
+                .await?;
+            return Ok(());
+        }
+    };
+    info!("{} requested to spark {}", author_name, query);
+
+    let entry = match select_banner(ctx, msg, args.rest()).await? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+    let language = preferred_language(msg);
+    let banner_name = translate(&entry.banner.name, language);
+
+    let student = match entry.find_sparkable(&query) {
+        Some(student) => student,
+        None => {
+            let sparkable = if entry.info.sparkable.is_empty() {
+                "Nobody can be sparked on this banner".to_string()
+            } else {
+                format!(
+                    "Sparkable students: {}",
+                    entry.sparkable_names(language).join(", ")
+                )
+            };
+
+            msg.reply(
+                ctx,
+                format!(
+                    "{} can't be sparked on {}. {}",
+                    query, banner_name, sparkable
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+
+    let result = store::update_user(msg.author.id, |user| {
+        if user.spend_points(&entry.info.id, SPARK_COST) {
+            recruit(user, &student, Utc::now());
+            Ok(user.points(&entry.info.id))
+        } else {
+            Err(user.points(&entry.info.id))
+        }
+    });
+
+    let points = match result {
+        Ok(points) => points,
+        Err(points) => {
+            msg.reply(
+                ctx,
+                format!(
+                    "You need {} recruitment points to spark, but only have {} on {}",
+                    SPARK_COST, points, banner_name
+                ),
+            )
+            .await?;
+            return Ok(());
+        }
+    };
+    info!(
+        "{} sparked {} on {}",
+        author_name, student.name, entry.info.id
+    );
+
+    let secondary_name = secondary(&student.name, language).unwrap_or_default();
+    let img_url = portrait_url(&student);
+    let icon_url = format!("{}/Icons/icon-brand.png", CDN_URL);
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .image(img_url)
+                    .title(format!(
+                        "{} has been recruited!",
+                        translate(&student.name, language)
+                    ))
+                    .description(format!(
+                        "{} exchanged {} points",
+                        secondary_name, SPARK_COST
+                    ))
+                    .field("Recruitment Points", format_points(points), true)
                     .footer(|footer| {
                         footer
                             .icon_url(icon_url)
                             .text("Image Source: https://thearchive.gg")
                     })
+                    .colour(get_rarity_colour(student.rarity))
             })
         })
         .await?;
+
+    Ok(())
+}
+
+pub async fn roll_until(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let query = match read_student(msg, &mut args, false) {
+        Some(query) => query,
+        None => {
+            msg.reply(ctx, "Tell アロナ which student you'd like to roll for")
+                .await?;
+            return Ok(());
+        }
+    };
+    info!("{} requested to roll until {}", author_name, query);
+
+    let entry = match select_banner(ctx, msg, args.rest()).await? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+
+    let target = match select_student(ctx, msg, &query).await? {
+        Some(info) => info.student,
+        None => return Ok(()),
+    };
+    let target_jpn = target.name.to_string();
+    let language = preferred_language(msg);
+    let target_name = translate(&target.name, language);
+    if !entry.info.contains(&target_jpn) {
+        msg.reply(
+            ctx,
+            format!(
+                "{} can't be pulled on {}",
+                target_name,
+                translate(&entry.banner.name, language)
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let typing = msg.channel_id.start_typing(&ctx.http)?;
+
+    let mut pulled: Vec<Student> = Vec::with_capacity(SPARK_COST as usize);
+    let mut pyroxene = None;
+    let mut out_of_pyroxene = false;
+
+    while pulled.len() < SPARK_COST as usize {
+        match economy::charge(msg, ROLL10_COST) {
+            Charge::Free => {}
+            Charge::Paid(left) => pyroxene = Some(left),
+            Charge::Insufficient(left) => {
+                pyroxene = Some(left);
+                out_of_pyroxene = true;
+                break;
+            }
+        }
+
+        let students = entry.banner.roll10();
+        store::update_user(msg.author.id, |user| {
+            let now = Utc::now();
+            for student in students.iter() {
+                recruit(user, student, now);
+            }
+            record_roll(user, &entry, &students, msg.guild_id, now);
+            user.add_points(&entry.info.id, 10);
+        });
+        pulled.extend(students.iter().cloned());
+
+        if students
+            .iter()
+            .any(|student| student.name == target_jpn.as_str())
+        {
+            break;
+        }
+    }
+
+    let found_at = pulled
+        .iter()
+        .position(|student| student.name == target_jpn.as_str());
+
+    // Exchange recruitment points for the student if they never showed up
+    let sparked = found_at.is_none()
+        && pulled.len() >= SPARK_COST as usize
+        && entry.info.sparkable.contains(&target_jpn)
+        && store::update_user(msg.author.id, |user| {
+            let sparked = user.spend_points(&entry.info.id, SPARK_COST);
+            if sparked {
+                recruit(user, &target, Utc::now());
+            }
+            sparked
+        });
+    let points = store::user(msg.author.id).points(&entry.info.id);
+
+    let outcome = match found_at {
+        Some(i) => format!("Recruited {} on pull {}", target_name, i + 1),
+        None if sparked => format!("Sparked {} after {} pulls", target_name, pulled.len()),
+        None if out_of_pyroxene => format!(
+            "Ran out of pyroxene after {} pulls without recruiting {}",
+            pulled.len(),
+            target_name
+        ),
+        None => format!("{} didn't show up in {} pulls", target_name, pulled.len()),
+    };
+
+    let three_stars: Vec<Student> = pulled
+        .iter()
+        .filter(|student| student.rarity == Rarity::Three)
+        .cloned()
+        .collect();
+    let three_star_names = if three_stars.is_empty() {
+        "None".to_string()
+    } else {
+        three_stars
+            .iter()
+            .map(|student| translate(&student.name, language))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    let collage = if three_stars.is_empty() {
+        None
+    } else {
+        match render_collage(&three_stars, 5).await {
+            Ok(jpeg) => Some(jpeg),
+            Err(err) => {
+                error!("Failed to Encode JPEG: {:?}", err);
+                None
+            }
+        }
+    };
+
+    let pulls = pulled.len() as u32;
+    let colour = if found_at.is_some() || sparked {
+        get_rarity_colour(target.rarity)
+    } else {
+        BLUE_ARCHIVE_BLUE
+    };
+    let title = format!(
+        "Rolling for {} on {}",
+        target_name,
+        translate(&entry.banner.name, language)
+    );
+    let build_embed = |embed: &mut CreateEmbed| {
+        embed
+            .title(&title)
+            .description(&outcome)
+            .field("Pulls", pulls, true)
+            .field("Pyroxene Equivalent", pulls / 10 * ROLL10_COST, true)
+            .field("Recruitment Points", format_points(points), true)
+            .field(
+                format!("3★ Students ({})", three_stars.len()),
+                &three_star_names,
+                false,
+            )
+            .colour(colour);
+
+        if let Some(pyroxene) = pyroxene {
+            embed.field("Pyroxene", pyroxene, true);
+        }
+    };
+
+    let _ = typing.stop();
+    match &collage {
+        Some(jpeg) => {
+            let files = vec![(jpeg.as_slice(), "result.jpeg")];
+            msg.channel_id
+                .send_files(ctx, files, |m| {
+                    m.embed(|embed| {
+                        build_embed(embed);
+                        embed.attachment("result.jpeg")
+                    })
+                })
+                .await?;
+        }
+        None => {
+            msg.channel_id
+                .send_message(ctx, |m| {
+                    m.embed(|embed| {
+                        build_embed(embed);
+                        embed
+                    })
+                })
+                .await?;
+        }
+    }
+
     Ok(())
 }
 
-pub async fn banner(ctx: &Context, msg: &Message) -> CommandResult {
+pub async fn banner(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
     let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let (region, query) = select_region(msg, args.rest());
+
+    if query.eq_ignore_ascii_case("list") {
+        info!("{} requested the list of {} banners", author_name, region);
+        return banner_list(ctx, msg, region).await;
+    }
+
+    if query.eq_ignore_ascii_case("history") {
+        info!("{} requested the {} banner history", author_name, region);
+        return banner_history(ctx, msg, region).await;
+    }
+
     info!("{} requested banner information", author_name);
 
+    let entry = match select_banner(ctx, msg, args.rest()).await? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+
+    let now = Utc::now();
     let channel = msg.channel_id;
-    let banner_eng = BANNER.name.get(Language::English).unwrap();
+    let language = preferred_language(msg);
+    let rate_ups = entry.rate_up_names(language).join("\n");
+    let festival = format!(
+        "3★ rate of {}% (usually {}%)",
+        entry.info.rates().three,
+        DEFAULT_RATES.three
+    );
+
+    let status = if entry.is_live(now) {
+        format!("Ends in {}", format_duration(entry.info.end - now))
+    } else if entry.info.start > now {
+        format!("Starts in {}", format_duration(entry.info.start - now))
+    } else {
+        format!("Ended on {}", entry.info.end.format("%Y-%m-%d"))
+    };
+
+    let upcoming = registry().upcoming(now, entry.info.region).map(|next| {
+        format!(
+            "{} ({})\nStarts in {}",
+            translate(&next.banner.name, language),
+            next.info.id,
+            format_duration(next.info.start - now)
+        )
+    });
 
     channel
         .send_message(ctx, |m| {
             m.embed(|embed| {
+                if let Some(img_url) = &entry.info.image {
+                    embed.image(img_url);
+                }
+
+                if entry.info.festival {
+                    embed.field("Festival", festival, false);
+                }
+
+                if !rate_ups.is_empty() {
+                    embed.field("Rate-up", rate_ups, false);
+                }
+
+                embed
+                    .field("Region", entry.info.region, true)
+                    .field("Schedule", status, true);
+
+                if let Some(upcoming) = upcoming {
+                    embed.field("Next Banner", upcoming, false);
+                }
+
                 embed
-                    .image(BANNER_IMG_URL)
-                    .title(BANNER.name.clone())
-                    .description(banner_eng)
+                    .title(translate(&entry.banner.name, language))
+                    .description(secondary(&entry.banner.name, language).unwrap_or_default())
+                    .footer(|footer| footer.text(format!("id: {}", entry.info.id)))
                     .colour(BLUE_ARCHIVE_BLUE)
             })
         })
@@ -174,297 +747,338 @@ pub async fn banner(ctx: &Context, msg: &Message) -> CommandResult {
     Ok(())
 }
 
-fn _create_2021_02_04_hoshino_shiroko_banner() -> Banner {
-    let three_stars = "ヒナ, イオリ, ハルナ, イズミ, アル, スミレ, エイミ, カリン, ネル, マキ, ヒビキ, サヤ, シュン, シロコ, ホシノ, ヒフミ, ツルギ";
-    let two_stars = "アカリ, ジュンコ, ムツキ, カヨコ, フウカ, ユウカ, アカネ, ハレ, ウタハ, チセ, ツバキ, セリカ, アヤネ, ハスミ, ハナエ, アイリ";
-    let one_stars =
-        "チナツ, ハルカ, ジュリ, コタマ, アスナ, コトリ, フィーナ, スズミ, シミコ, セリナ, ヨシミ";
+async fn banner_list(ctx: &Context, msg: &Message, region: Region) -> CommandResult {
+    let now = Utc::now();
+    let channel = msg.channel_id;
+    let language = preferred_language(msg);
+    let registry = registry();
+    let default_id = registry
+        .default_banner(now, region)
+        .map(|entry| entry.info.id.clone());
+
+    let mut fields: Vec<(String, String, bool)> = registry
+        .live(now, region)
+        .map(|entry| {
+            let secondary_name = secondary(&entry.banner.name, language).unwrap_or_default();
+            let mut marker = String::new();
+            if entry.info.festival {
+                marker.push_str(" (festival)");
+            }
+            if default_id.as_ref() == Some(&entry.info.id) {
+                marker.push_str(" (default)");
+            }
 
-    let mut pool = Vec::new();
-    pool.extend(get_students(three_stars));
-    pool.extend(get_students(two_stars));
-    pool.extend(get_students(one_stars));
+            let mut value = format!(
+                "`{}`{}\n{}\nEnds in {}",
+                entry.info.id,
+                marker,
+                secondary_name,
+                format_duration(entry.info.end - now)
+            );
+            let rate_ups = entry.rate_up_names(language);
+            if !rate_ups.is_empty() {
+                value.push_str(&format!("\nRate-up: {}", rate_ups.join(", ")));
+            }
 
-    let hoshino = pool
-        .iter()
-        .find(|student| student.name == "ホシノ")
-        .cloned()
-        .unwrap()
-        .into_priority_student(0.7 / 2.0);
+            (translate(&entry.banner.name, language), value, false)
+        })
+        .collect();
+
+    let description = match (&default_id, fields.is_empty()) {
+        (None, _) => format!("There are no {} banners yet", region),
+        (Some(default_id), true) => format!(
+            "No banners are running right now, so {} is used by default",
+            default_id
+        ),
+        (Some(_), false) => "Pass a banner's id or name to !roll, !roll10 or !banner".to_string(),
+    };
 
-    let shiroko = pool
-        .iter()
-        .find(|student| student.name == "シロコ")
-        .cloned()
-        .unwrap()
-        .into_priority_student(0.7 / 2.0);
-
-    let sparkable = vec![hoshino.student().clone(), shiroko.student().clone()];
-    let priority = vec![hoshino, shiroko];
-
-    let gacha = GachaBuilder::default()
-        .with_pool(pool)
-        .with_priority(priority)
-        .finish()
-        .unwrap();
-
-    BannerBuilder::new("拝啓、はじまりの季節へ")
-        .with_gacha(gacha)
-        .with_name_translation(Language::English, "Dear Sensei, a new season approaches")
-        .with_sparkable_students(sparkable)
-        .finish()
-        .unwrap()
+    if let Some(next) = registry.upcoming(now, region) {
+        fields.push((
+            format!("Next: {}", translate(&next.banner.name, language)),
+            format!(
+                "`{}`\nStarts in {}",
+                next.info.id,
+                format_duration(next.info.start - now)
+            ),
+            false,
+        ));
+    }
+
+    channel
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!("Live {} Banners", region))
+                    .description(description)
+                    .fields(fields)
+                    .colour(BLUE_ARCHIVE_BLUE)
+            })
+        })
+        .await?;
+
+    Ok(())
 }
 
-fn _create_2021_02_11_mashiro_banner() -> Banner {
-    let three_stars = "ヒナ, イオリ, ハルナ, イズミ, アル, スミレ, エイミ, カリン, ネル, マキ, ヒビキ, サヤ, シュン, シロコ, ホシノ, ヒフミ, ツルギ, マシロ";
-    let two_stars = "アカリ, ジュンコ, ムツキ, カヨコ, フウカ, ユウカ, アカネ, ハレ, ウタハ, チセ, ツバキ, セリカ, アヤネ, ハスミ, ハナエ, アイリ";
-    let one_stars =
-        "チナツ, ハルカ, ジュリ, コタマ, アスナ, コトリ, フィーナ, スズミ, シミコ, セリナ, ヨシミ";
+async fn banner_history(ctx: &Context, msg: &Message, region: Region) -> CommandResult {
+    const MAX_FIELDS: usize = 25; // Discord's limit on fields in an embed
 
-    let mut pool = Vec::new();
-    pool.extend(get_students(three_stars));
-    pool.extend(get_students(two_stars));
-    pool.extend(get_students(one_stars));
+    let channel = msg.channel_id;
+    let language = preferred_language(msg);
+
+    let fields: Vec<(String, String, bool)> = registry()
+        .archived(Utc::now(), region)
+        .take(MAX_FIELDS)
+        .map(|entry| {
+            let secondary_name = secondary(&entry.banner.name, language).unwrap_or_default();
+
+            let marker = if entry.info.festival {
+                " (festival)"
+            } else {
+                ""
+            };
+
+            let mut value = format!(
+                "`{}`{}\n{}\n{} ~ {}",
+                entry.info.id,
+                marker,
+                secondary_name,
+                entry.info.start.format("%Y-%m-%d"),
+                entry.info.end.format("%Y-%m-%d")
+            );
+            let rate_ups = entry.rate_up_names(language);
+            if !rate_ups.is_empty() {
+                value.push_str(&format!("\nRate-up: {}", rate_ups.join(", ")));
+            }
 
-    let mashiro = pool
-        .iter()
-        .find(|student| student.name == "マシロ")
-        .cloned()
-        .unwrap()
-        .into_priority_student(0.7);
-
-    let sparkable = vec![mashiro.student().clone()];
-    let priority = vec![mashiro];
-
-    let gacha = GachaBuilder::default()
-        .with_pool(pool)
-        .with_priority(priority)
-        .finish()
-        .unwrap();
-
-    BannerBuilder::new("赤の季節、黒の制服")
-        .with_gacha(gacha)
-        .with_name_translation(Language::English, "Red Season, Black Uniform")
-        .with_sparkable_students(sparkable)
-        .finish()
-        .unwrap()
+            (translate(&entry.banner.name, language), value, false)
+        })
+        .collect();
+
+    let description = if fields.is_empty() {
+        "No banners have ended yet"
+    } else {
+        "Past banners can still be rolled on by passing their id or name to !roll or !roll10"
+    };
+
+    channel
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!("{} Banner History", region))
+                    .description(description)
+                    .fields(fields)
+                    .colour(BLUE_ARCHIVE_BLUE)
+            })
+        })
+        .await?;
+
+    Ok(())
 }
 
-fn _create_2021_02_25_izuna_banner() -> Banner {
-    let three_stars = "ヒナ, イオリ, ハルナ, イズミ, アル, スミレ, エイミ, カリン, ネル, マキ, ヒビキ, サヤ, シュン, シロコ, ホシノ, ヒフミ, ツルギ, マシロ, イズナ";
-    let two_stars = "アカリ, ジュンコ, ムツキ, カヨコ, フウカ, ユウカ, アカネ, ハレ, ウタハ, チセ, ツバキ, セリカ, アヤネ, ハスミ, ハナエ, アイリ, シズコ";
-    let one_stars =
-        "チナツ, ハルカ, ジュリ, コタマ, アスナ, コトリ, フィーナ, スズミ, シミコ, セリナ, ヨシミ";
+/// Downloads every student's portrait and lays them out in rows of `columns`, encoded as a JPEG
+async fn render_collage(students: &[Student], columns: u32) -> image::ImageResult<Vec<u8>> {
+    let rows = (students.len() as u32).div_ceil(columns);
+    let width = THUMB_WIDTH * columns.min(students.len() as u32);
+    let height = THUMB_HEIGHT * rows;
 
-    let mut pool = Vec::new();
-    pool.extend(get_students(three_stars));
-    pool.extend(get_students(two_stars));
-    pool.extend(get_students(one_stars));
+    let mut collage = RgbaImage::new(width, height);
 
-    let shizuko = pool
-        .iter()
-        .find(|student| student.name == "シズコ")
-        .cloned()
-        .unwrap()
-        .into_priority_student(3.0);
+    let start = Instant::now();
+    for (i, student) in students.iter().enumerate() {
+        let img_url = portrait_url(student);
+        let image = get_image_from_url(&img_url, THUMB_WIDTH, THUMB_HEIGHT).await;
 
-    let izuna = pool
-        .iter()
-        .find(|student| student.name == "イズナ")
-        .cloned()
-        .unwrap()
-        .into_priority_student(0.7);
-
-    let sparkable = vec![izuna.student().clone()];
-    let priority = vec![izuna, shizuko];
-
-    let gacha = GachaBuilder::default()
-        .with_pool(pool)
-        .with_priority(priority)
-        .finish()
-        .unwrap();
-
-    BannerBuilder::new("祭り囃子はしのぶれど")
-        .with_gacha(gacha)
-        .with_name_translation(Language::English, "The Concealed Festival Band")
-        .with_sparkable_students(sparkable)
-        .finish()
-        .unwrap()
-}
+        let (x, y) = (i as u32 % columns, i as u32 / columns);
+        image::imageops::overlay(&mut collage, &image, x * THUMB_WIDTH, y * THUMB_HEIGHT);
+    }
+    let elapsed_ms = (Instant::now() - start).as_millis();
+    info!(
+        "Collage of {} students took {}ms",
+        students.len(),
+        elapsed_ms
+    );
 
-fn _create_2021_03_11_haruna_banner() -> Banner {
-    let three_stars = "ヒナ, イオリ, ハルナ, イズミ, アル, スミレ, エイミ, カリン, ネル, マキ, ヒビキ, サヤ, シュン, シロコ, ホシノ, ヒフミ, ツルギ, マシロ, イズナ";
-    let two_stars = "アカリ, ジュンコ, ムツキ, カヨコ, フウカ, ユウカ, アカネ, ハレ, ウタハ, チセ, ツバキ, セリカ, アヤネ, ハスミ, ハナエ, アイリ, シズコ";
-    let one_stars =
-        "チナツ, ハルカ, ジュリ, コタマ, アスナ, コトリ, フィーナ, スズミ, シミコ, セリナ, ヨシミ";
+    let mut jpeg = Vec::new();
+    JpegEncoder::new(&mut jpeg).write_image(&collage, width, height, ColorType::Rgba8)?;
 
-    let mut pool = Vec::new();
-    pool.extend(get_students(three_stars));
-    pool.extend(get_students(two_stars));
-    pool.extend(get_students(one_stars));
+    Ok(jpeg)
+}
 
-    let haruna = pool
-        .iter()
-        .find(|student| student.name == "ハルナ")
-        .cloned()
-        .unwrap()
-        .into_priority_student(0.7);
-
-    let sparkable = vec![haruna.student().clone()];
-    let priority = vec![haruna];
-
-    let gacha = GachaBuilder::default()
-        .with_pool(pool)
-        .with_priority(priority)
-        .finish()
-        .unwrap();
-
-    BannerBuilder::new("その味は身命を賭してでも")
-        .with_gacha(gacha)
-        .with_name_translation(
-            Language::English,
-            "Even if you risk your life for that taste",
-        )
-        .with_sparkable_students(sparkable)
-        .finish()
-        .unwrap()
+/// Pays for a roll, telling the user if they can't afford it
+pub(crate) async fn pay(
+    ctx: &Context,
+    msg: &Message,
+    cost: u32,
+) -> serenity::Result<Option<Charge>> {
+    match economy::charge(msg, cost) {
+        Charge::Insufficient(pyroxene) => {
+            msg.reply(
+                ctx,
+                format!(
+                    "You need {} pyroxene for that, but only have {}. Try `!daily`",
+                    cost, pyroxene
+                ),
+            )
+            .await?;
+            Ok(None)
+        }
+        charge => Ok(Some(charge)),
+    }
 }
 
-fn _create_2021_03_18_aru_banner() -> Banner {
-    let three_stars = "ヒナ, イオリ, ハルナ, イズミ, アル, スミレ, エイミ, カリン, ネル, マキ, ヒビキ, サヤ, シュン, シロコ, ホシノ, ヒフミ, ツルギ, マシロ, イズナ";
-    let two_stars = "アカリ, ジュンコ, ムツキ, カヨコ, フウカ, ユウカ, アカネ, ハレ, ウタハ, チセ, ツバキ, セリカ, アヤネ, ハスミ, ハナエ, アイリ, シズコ";
-    let one_stars =
-        "チナツ, ハルカ, ジュリ, コタマ, アスナ, コトリ, フィーナ, スズミ, シミコ, セリナ, ヨシミ";
+/// Picks the game server a command is about: a region named in its arguments (e.g. `global`),
+/// or else the one chosen for the Discord server. Returns it along with the rest of the arguments
+pub(crate) fn select_region(msg: &Message, args: &str) -> (Region, String) {
+    let mut region = None;
+    let mut words = Vec::new();
+
+    for word in args.split_whitespace() {
+        match Region::parse(word) {
+            Some(parsed) if region.is_none() => region = Some(parsed),
+            _ => words.push(word),
+        }
+    }
 
-    let mut pool = Vec::new();
-    pool.extend(get_students(three_stars));
-    pool.extend(get_students(two_stars));
-    pool.extend(get_students(one_stars));
+    let region = region.unwrap_or_else(|| {
+        msg.guild_id
+            .map(|guild_id| store::guild(guild_id).region)
+            .unwrap_or_default()
+    });
 
-    let aru = pool
-        .iter()
-        .find(|student| student.name == "アル")
-        .cloned()
-        .unwrap()
-        .into_priority_student(0.7);
-
-    let sparkable = vec![aru.student().clone()];
-    let priority = vec![aru];
-
-    let gacha = GachaBuilder::default()
-        .with_pool(pool)
-        .with_priority(priority)
-        .finish()
-        .unwrap();
-
-    BannerBuilder::new("悪は一日にして成らず")
-        .with_gacha(gacha)
-        .with_name_translation(Language::English, "Evil is not attained in a single day")
-        .with_sparkable_students(sparkable)
-        .finish()
-        .unwrap()
+    (region, words.join(" "))
 }
 
-fn _create_2021_03_25_arisu_banner() -> Banner {
-    let three_stars = "ヒナ, イオリ, ハルナ, イズミ, アル, スミレ, エイミ, カリン, ネル, マキ, ヒビキ, サヤ, シュン, シロコ, ホシノ, ヒフミ, ツルギ, マシロ, イズナ, アリス";
-    let two_stars = "アカリ, ジュンコ, ムツキ, カヨコ, フウカ, ユウカ, アカネ, ハレ, ウタハ, チセ, ツバキ, セリカ, アヤネ, ハスミ, ハナエ, アイリ, シズコ";
-    let one_stars =
-        "チナツ, ハルカ, ジュリ, コタマ, アスナ, コトリ, フィーナ, スズミ, シミコ, セリナ, ヨシミ";
+/// Finds the banner requested by a command, telling the user if it doesn't exist
+pub(crate) async fn select_banner(
+    ctx: &Context,
+    msg: &Message,
+    query: &str,
+) -> serenity::Result<Option<Arc<BannerEntry>>> {
+    let (region, query) = select_region(msg, query);
+
+    match registry().select(&query, Utc::now(), region) {
+        Some(entry) => Ok(Some(entry.clone())),
+        None if query.trim().is_empty() => {
+            msg.reply(ctx, format!("There are no {} banners yet", region))
+                .await?;
+            Ok(None)
+        }
+        None => {
+            msg.reply(
+                ctx,
+                format!(
+                    "アロナ couldn't find a banner called \"{}\". Use `!banner list` or `!banner history` to see every banner",
+                    query.trim()
+                ),
+            )
+            .await?;
+            Ok(None)
+        }
+    }
+}
 
-    let mut pool = Vec::new();
-    pool.extend(get_students(three_stars));
-    pool.extend(get_students(two_stars));
-    pool.extend(get_students(one_stars));
+/// Reads the student at the front of a command's arguments, e.g. the Hina (Swimsuit) of
+/// `!spark Hina (Swimsuit) summer`. Names with spaces don't have to be quoted: the student is as
+/// few words as leave a banner (or nothing) behind. With `counted`, a number may come between the
+/// two, like in `!simulate Hina (Swimsuit) 500 summer`
+pub(crate) fn read_student(msg: &Message, args: &mut Args, counted: bool) -> Option<String> {
+    let registry = registry();
+    let mut words = Vec::new();
+
+    while let Ok(word) = args.quoted().single::<String>() {
+        words.push(word);
+
+        let mut rest = args.clone();
+        if counted {
+            parse_count(&mut rest);
+        }
+        let (region, query) = select_region(msg, rest.rest());
+        if query.trim().is_empty() || registry.find(&query, Utc::now(), region).is_some() {
+            break;
+        }
+    }
 
-    let arisu = pool
-        .iter()
-        .find(|student| student.name == "アリス")
-        .cloned()
-        .unwrap()
-        .into_priority_student(0.7);
-
-    let sparkable = vec![arisu.student().clone()];
-    let priority = vec![arisu];
-
-    let gacha = GachaBuilder::default()
-        .with_pool(pool)
-        .with_priority(priority)
-        .finish()
-        .unwrap();
-
-    BannerBuilder::new("機械たちは旅立ちの夢を見るか？")
-        .with_gacha(gacha)
-        .with_name_translation(Language::English, "Do Machines Dream of Departure?")
-        .with_sparkable_students(sparkable)
-        .finish()
-        .unwrap()
+    if words.is_empty() {
+        None
+    } else {
+        Some(words.join(" "))
+    }
+}
+
+/// Reads a number from the front of a command's arguments, e.g. the 10 of `!roll 10 summer`.
+/// A failed parse doesn't consume the argument, so it can still be read as the banner
+pub(crate) fn parse_count(args: &mut Args) -> Option<u32> {
+    args.single::<u32>().ok()
 }
 
-fn create_2021_04_08_midori_banner() -> Banner {
-    let three_stars = "ヒナ, イオリ, ハルナ, イズミ, アル, スミレ, エイミ, カリン, ネル, マキ, ヒビキ, サヤ, シュン, シロコ, ホシノ, ヒフミ, ツルギ, マシロ, イズナ, アリス, ミドリ";
-    let two_stars = "アカリ, ジュンコ, ムツキ, カヨコ, フウカ, ユウカ, アカネ, ハレ, ウタハ, チセ, ツバキ, セリカ, アヤネ, ハスミ, ハナエ, アイリ, シズコ, モモイ";
-    let one_stars =
-        "チナツ, ハルカ, ジュリ, コタマ, アスナ, コトリ, フィーナ, スズミ, シミコ, セリナ, ヨシミ";
+/// Joins a list of lines so they fit in an embed field
+pub(crate) fn list_or_none(lines: &[String]) -> String {
+    const MAX_FIELD_LEN: usize = 1024;
 
-    let mut pool = Vec::new();
-    pool.extend(get_students(three_stars));
-    pool.extend(get_students(two_stars));
-    pool.extend(get_students(one_stars));
+    if lines.is_empty() {
+        return "None".to_string();
+    }
 
-    let momoi = pool
-        .iter()
-        .find(|student| student.name == "モモイ")
-        .cloned()
-        .unwrap()
-        .into_priority_student(3.0);
+    let mut text = String::new();
+    for (i, line) in lines.iter().enumerate() {
+        // Leave room for the "…and N more" line
+        if text.len() + line.len() + 1 > MAX_FIELD_LEN - 32 {
+            text.push_str(&format!("…and {} more", lines.len() - i));
+            break;
+        }
 
-    let midori = pool
-        .iter()
-        .find(|student| student.name == "ミドリ")
-        .cloned()
-        .unwrap()
-        .into_priority_student(0.7);
-
-    let sparkable = vec![midori.student().clone()];
-    let priority = vec![midori, momoi];
-
-    let gacha = GachaBuilder::default()
-        .with_pool(pool)
-        .with_priority(priority)
-        .finish()
-        .unwrap();
-
-    BannerBuilder::new("共鳴する風信子")
-        .with_gacha(gacha)
-        .with_name_translation(Language::English, "A Hyacinth swaying in the wind")
-        .with_sparkable_students(sparkable)
-        .finish()
-        .unwrap()
+        text.push_str(line);
+        text.push('\n');
+    }
+
+    text
 }
 
-fn get_students(student_list: &str) -> Vec<Student> {
-    let names = student_list.split(", ");
-    let mut students = match names.size_hint().1 {
-        Some(size) => Vec::with_capacity(size),
-        None => Vec::new(),
-    };
+pub(crate) fn format_points(points: u32) -> String {
+    format!("{}/{}", points, SPARK_COST)
+}
 
-    for name in names {
-        let maybe_student = STUDENTS.iter().find(|student| student.name == name);
+/// The URL of a student's portrait on the CDN
+pub(crate) fn portrait_url(student: &Student) -> String {
+    format!(
+        "{}/Characters/{}.png",
+        CDN_URL,
+        encode_path(&image_key(student))
+    )
+}
 
-        match maybe_student {
-            Some(student) => {
-                students.push(student.clone());
+/// The URL of a student's page on https://thearchive.gg
+pub(crate) fn page_url(student: &Student) -> String {
+    format!(
+        "https://www.thearchive.gg/characters/{}",
+        encode_path(&image_key(student))
+    )
+}
+
+/// Percent-encodes everything but unreserved characters, so that e.g. spaces and parentheses
+/// can be used in a URL's path
+fn encode_path(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
             }
-            None => error!("Could not find {} in students.json", name),
-        };
-    }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
 
-    students
+pub(crate) fn get_rarity_stars(rarity: Rarity) -> &'static str {
+    match rarity {
+        Rarity::One => ":star:",
+        Rarity::Two => ":star::star:",
+        Rarity::Three => ":star::star::star:",
+    }
 }
 
-fn get_rarity_colour(rarity: Rarity) -> Colour {
+pub(crate) fn get_rarity_colour(rarity: Rarity) -> Colour {
     match rarity {
         Rarity::One => Colour::from_rgb(227, 234, 240),
         Rarity::Two => Colour::from_rgb(255, 248, 124),
@@ -0,0 +1,203 @@
+use crate::banner::BannerEntry;
+use crate::economy::ROLL_COST;
+use crate::general::BLUE_ARCHIVE_BLUE;
+use crate::language::{display_name, preferred_language, translate};
+use crate::lookup::select_student;
+use crate::recruitment::{parse_count, read_student, select_banner, SPARK_COST};
+use blue_gacha::gacha::Recruitment as RecruitmentTrait;
+use log::info;
+use serenity::client::Context;
+use serenity::framework::standard::{Args, CommandResult};
+use serenity::model::channel::Message;
+use std::time::Instant;
+
+const DEFAULT_TRIALS: u32 = 1000;
+const MAX_TRIALS: u32 = 10000;
+const GIVE_UP_PULLS: u32 = 1000; // Where trials for students that can't be sparked stop
+
+/// How many pulls every trial needed to recruit a student
+pub struct Simulation {
+    /// Sorted from fewest to most pulls
+    pulls: Vec<u32>,
+    /// Trials which reached the spark before pulling the student
+    pub sparked: u32,
+    /// Trials which gave up without ever recruiting the student
+    pub gave_up: u32,
+}
+
+impl Simulation {
+    /// Rolls 10-rolls on `entry` until `target` (a Japanese name) is pulled, `trials` times
+    pub fn run(entry: &BannerEntry, target: &str, trials: u32) -> Self {
+        let sparkable = entry.info.sparkable.iter().any(|name| name == target);
+        let limit = if sparkable { SPARK_COST } else { GIVE_UP_PULLS };
+
+        let mut pulls = Vec::with_capacity(trials as usize);
+        let mut sparked = 0;
+        let mut gave_up = 0;
+
+        for _ in 0..trials {
+            let mut count = 0;
+
+            let found = loop {
+                if count >= limit {
+                    break false;
+                }
+
+                let students = entry.banner.roll10();
+                match students.iter().position(|student| student.name == target) {
+                    Some(i) => {
+                        count += i as u32 + 1;
+                        break true;
+                    }
+                    None => count += 10,
+                }
+            };
+
+            if !found {
+                if sparkable {
+                    sparked += 1;
+                } else {
+                    gave_up += 1;
+                }
+            }
+
+            pulls.push(count.min(limit));
+        }
+        pulls.sort_unstable();
+
+        Self {
+            pulls,
+            sparked,
+            gave_up,
+        }
+    }
+
+    pub fn trials(&self) -> u32 {
+        self.pulls.len() as u32
+    }
+
+    /// The number of pulls needed by `percent`% of trials
+    pub fn percentile(&self, percent: f64) -> u32 {
+        if self.pulls.is_empty() {
+            return 0;
+        }
+
+        let rank = (percent / 100.0 * self.pulls.len() as f64).ceil() as usize;
+        self.pulls[rank.clamp(1, self.pulls.len()) - 1]
+    }
+}
+
+pub async fn simulate(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let query = match read_student(msg, &mut args, true) {
+        Some(query) => query,
+        None => {
+            msg.reply(ctx, "Tell アロナ which student you'd like to simulate")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let trials = parse_count(&mut args)
+        .map(|trials| trials.clamp(1, MAX_TRIALS))
+        .unwrap_or(DEFAULT_TRIALS);
+    info!(
+        "{} requested {} simulations for {}",
+        author_name, trials, query
+    );
+
+    let entry = match select_banner(ctx, msg, args.rest()).await? {
+        Some(entry) => entry,
+        None => return Ok(()),
+    };
+
+    let student = match select_student(ctx, msg, &query).await? {
+        Some(info) => info.student,
+        None => return Ok(()),
+    };
+    let language = preferred_language(msg);
+    if !entry.info.contains(&student.name.to_string()) {
+        msg.reply(
+            ctx,
+            format!(
+                "{} can't be pulled on {}",
+                translate(&student.name, language),
+                translate(&entry.banner.name, language)
+            ),
+        )
+        .await?;
+        return Ok(());
+    }
+    let target = student.name.to_string();
+
+    let typing = msg.channel_id.start_typing(&ctx.http)?;
+
+    let start = Instant::now();
+    let simulation = {
+        let (entry, target) = (entry.clone(), target.clone());
+        tokio::task::spawn_blocking(move || Simulation::run(&entry, &target, trials)).await?
+    };
+    info!(
+        "{} simulations took {}ms",
+        trials,
+        (Instant::now() - start).as_millis()
+    );
+
+    let format_pulls = |pulls: u32| format!("{} pulls ({} pyroxene)", pulls, pulls * ROLL_COST);
+
+    let mut notes = Vec::new();
+    if simulation.sparked > 0 {
+        notes.push(format!(
+            "{:.1}% of trials needed the {}-pull spark",
+            simulation.sparked as f64 / simulation.trials() as f64 * 100.0,
+            SPARK_COST
+        ));
+    }
+    if simulation.gave_up > 0 {
+        notes.push(format!(
+            "{:.1}% of trials didn't get {} within {} pulls",
+            simulation.gave_up as f64 / simulation.trials() as f64 * 100.0,
+            translate(&student.name, language),
+            GIVE_UP_PULLS
+        ));
+    }
+
+    let _ = typing.stop();
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!(
+                        "Recruiting {}",
+                        display_name(&student.name, language)
+                    ))
+                    .description(format!(
+                        "{} simulated trials on {}",
+                        simulation.trials(),
+                        translate(&entry.banner.name, language)
+                    ))
+                    .field("Median", format_pulls(simulation.percentile(50.0)), true)
+                    .field(
+                        "90th Percentile",
+                        format_pulls(simulation.percentile(90.0)),
+                        true,
+                    )
+                    .field(
+                        "99th Percentile",
+                        format_pulls(simulation.percentile(99.0)),
+                        true,
+                    )
+                    .colour(BLUE_ARCHIVE_BLUE);
+
+                if !notes.is_empty() {
+                    embed.field("Notes", notes.join("\n"), false);
+                }
+
+                embed
+            })
+        })
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,443 @@
+use crate::banner::{registry, BannerEntry};
+use crate::general::BLUE_ARCHIVE_BLUE;
+use crate::language::{preferred_language, translate};
+use crate::recruitment::{select_banner, select_region, SPARK_COST};
+use crate::store::{self, rarity_level, Pull, RollRecord, UserData};
+use blue_gacha::student::Student;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use serenity::client::Context;
+use serenity::framework::standard::{Args, CommandResult};
+use serenity::model::channel::Message;
+use serenity::model::id::GuildId;
+use std::collections::HashMap;
+
+const MAX_FIELDS: usize = 24; // Discord allows 25, and one is taken by the overall stats
+const LEADERBOARD_SIZE: usize = 10;
+const MIN_PULLS: u32 = 10; // A single lucky roll shouldn't top the leaderboards
+
+/// What the leaderboard ranks users by
+#[derive(Clone, Copy, PartialEq)]
+enum Metric {
+    ThreeStarRate,
+    RateUps,
+    PullsToRateUp,
+}
+
+/// Which rolls the leaderboard takes into account
+#[derive(Clone, Copy, PartialEq)]
+enum Window {
+    AllTime,
+    CurrentBanner,
+    LastWeek,
+}
+
+/// Totals computed from a user's roll history
+#[derive(Default, Clone, Copy)]
+pub struct RollStats {
+    pub pulls: u32,
+    pub three: u32,
+    pub two: u32,
+    pub one: u32,
+    pub rate_up: u32,
+    /// The most pulls in a row without a 3★
+    pub longest_drought: u32,
+}
+
+impl RollStats {
+    pub fn from_records<'a>(records: impl IntoIterator<Item = &'a RollRecord>) -> Self {
+        let mut stats = Self::default();
+        let mut drought = 0;
+
+        for pull in records.into_iter().flat_map(|record| record.pulls.iter()) {
+            stats.pulls += 1;
+
+            match pull.rarity {
+                3 => stats.three += 1,
+                2 => stats.two += 1,
+                _ => stats.one += 1,
+            }
+
+            if pull.rate_up {
+                stats.rate_up += 1;
+            }
+
+            if pull.rarity == 3 {
+                drought = 0;
+            } else {
+                drought += 1;
+                stats.longest_drought = stats.longest_drought.max(drought);
+            }
+        }
+
+        stats
+    }
+
+    /// The observed 3★ rate in percent
+    pub fn three_star_rate(&self) -> f64 {
+        if self.pulls == 0 {
+            0.0
+        } else {
+            self.three as f64 / self.pulls as f64 * 100.0
+        }
+    }
+}
+
+/// Appends the result of a roll to a user's history
+pub fn record_roll(
+    user: &mut UserData,
+    entry: &BannerEntry,
+    students: &[Student],
+    guild: Option<GuildId>,
+    now: DateTime<Utc>,
+) {
+    let pulls = students
+        .iter()
+        .map(|student| Pull {
+            name: student.name.to_string(),
+            rarity: rarity_level(student.rarity),
+            rate_up: entry.info.is_rate_up(student),
+        })
+        .collect();
+
+    user.push_history(RollRecord {
+        banner: entry.info.id.clone(),
+        time: now,
+        guild: guild.map(|guild_id| guild_id.0),
+        pulls,
+    });
+}
+
+pub async fn stats(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+    info!("{} requested roll statistics", author_name);
+
+    let user = msg.mentions.first().unwrap_or(&msg.author);
+    let data = store::user(user.id);
+
+    let query = strip_mentions(args.rest());
+    let banner_id = if query.is_empty() {
+        None
+    } else {
+        match select_banner(ctx, msg, &query).await? {
+            Some(entry) => Some(entry.info.id.clone()),
+            None => return Ok(()),
+        }
+    };
+
+    // Group the history by banner, keeping the banners in the order they were first rolled on
+    let mut order: Vec<&str> = Vec::new();
+    let mut by_banner: HashMap<&str, Vec<&RollRecord>> = HashMap::new();
+    for record in data.history.iter() {
+        if let Some(id) = &banner_id {
+            if id != &record.banner {
+                continue;
+            }
+        }
+
+        let records = by_banner.entry(&record.banner).or_insert_with(|| {
+            order.push(&record.banner);
+            Vec::new()
+        });
+        records.push(record);
+    }
+
+    if order.is_empty() {
+        msg.reply(ctx, format!("{} hasn't rolled on that yet", user.name))
+            .await?;
+        return Ok(());
+    }
+
+    let overall = RollStats::from_records(
+        data.history
+            .iter()
+            .filter(|record| by_banner.contains_key(record.banner.as_str())),
+    );
+    let mut fields = vec![("Overall".to_string(), format_stats(&overall, None), false)];
+
+    let language = preferred_language(msg);
+    for id in order.iter().rev().take(MAX_FIELDS) {
+        let stats = RollStats::from_records(by_banner[id].iter().copied());
+        let (name, expected) = match registry().get(id) {
+            Some(entry) => (
+                translate(&entry.banner.name, language),
+                Some(entry.info.rates().three),
+            ),
+            None => (id.to_string(), None),
+        };
+
+        fields.push((name, format_stats(&stats, expected), false));
+    }
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(format!("{}'s Recruitment Statistics", user.name))
+                    .fields(fields)
+                    .colour(BLUE_ARCHIVE_BLUE)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn leaderboard(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+    info!("{} requested the leaderboard", author_name);
+
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id.0,
+        None => return Ok(()),
+    };
+
+    let (region, args) = select_region(msg, args.rest());
+
+    let mut metric = Metric::ThreeStarRate;
+    let mut window = Window::AllTime;
+    for word in args.split_whitespace() {
+        match word.to_lowercase().as_str() {
+            "rate" | "3*" | "luck" => metric = Metric::ThreeStarRate,
+            "rateup" | "rate-up" | "pickup" => metric = Metric::RateUps,
+            "spark" => metric = Metric::PullsToRateUp,
+            "all" | "alltime" | "all-time" => window = Window::AllTime,
+            "banner" | "current" => window = Window::CurrentBanner,
+            "week" | "7d" => window = Window::LastWeek,
+            _ => {
+                msg.reply(
+                    ctx,
+                    "Usage: `!leaderboard [rate | rateup | spark] [all | banner | week] [jp | global]`",
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    }
+
+    let now = Utc::now();
+    let current_banner = match (window, registry().default_banner(now, region)) {
+        (Window::CurrentBanner, None) => {
+            msg.reply(ctx, format!("There are no {} banners yet", region))
+                .await?;
+            return Ok(());
+        }
+        (_, entry) => entry.cloned(),
+    };
+    let in_window = |record: &RollRecord| {
+        record.guild == Some(guild_id)
+            && match window {
+                Window::AllTime => true,
+                Window::CurrentBanner => {
+                    current_banner.as_ref().map(|entry| &entry.info.id) == Some(&record.banner)
+                }
+                Window::LastWeek => now - record.time <= Duration::days(7),
+            }
+    };
+
+    let mut scores: Vec<(u64, f64, String)> = store::read(|store| {
+        store
+            .users
+            .iter()
+            .filter_map(|(&id, user)| {
+                let records: Vec<&RollRecord> = user
+                    .history
+                    .iter()
+                    .filter(|record| in_window(record))
+                    .collect();
+                let stats = RollStats::from_records(records.iter().copied());
+
+                match metric {
+                    Metric::ThreeStarRate if stats.pulls >= MIN_PULLS => {
+                        let rate = stats.three_star_rate();
+                        let text = format!("{:.2}% ({}/{} pulls)", rate, stats.three, stats.pulls);
+                        Some((id, rate, text))
+                    }
+                    Metric::RateUps if stats.rate_up > 0 => {
+                        let text = format!("{} rate-ups in {} pulls", stats.rate_up, stats.pulls);
+                        Some((id, stats.rate_up as f64, text))
+                    }
+                    Metric::PullsToRateUp => pulls_to_rate_up(&records).map(|pulls| {
+                        let text = format!("{:.1} pulls on average", pulls);
+                        // Fewer pulls is better, so flip the sign to share the sorting below
+                        (id, -pulls, text)
+                    }),
+                    _ => None,
+                }
+            })
+            .collect()
+    });
+    scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+    let title = match metric {
+        Metric::ThreeStarRate => "3★ Rate Leaderboard",
+        Metric::RateUps => "Rate-up Leaderboard",
+        Metric::PullsToRateUp => "Fewest Pulls to a Rate-up Leaderboard",
+    };
+    let window_str = match window {
+        Window::AllTime => "All-time".to_string(),
+        Window::CurrentBanner => current_banner
+            .as_ref()
+            .map(|entry| {
+                format!(
+                    "On {}",
+                    translate(&entry.banner.name, preferred_language(msg))
+                )
+            })
+            .unwrap_or_default(),
+        Window::LastWeek => "Last 7 days".to_string(),
+    };
+
+    let description = if scores.is_empty() {
+        "Nobody has qualified yet".to_string()
+    } else {
+        scores
+            .iter()
+            .take(LEADERBOARD_SIZE)
+            .enumerate()
+            .map(|(i, (id, _, text))| format!("**{}.** <@{}> — {}", i + 1, id, text))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title(title)
+                    .description(description)
+                    .footer(|footer| footer.text(window_str))
+                    .colour(BLUE_ARCHIVE_BLUE)
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// How many pulls a user needed on average to get a rate-up student on each banner.
+/// A banner with [`SPARK_COST`] pulls and no rate-up counts as a spark, and banners which were
+/// rolled on too little to have either are skipped
+fn pulls_to_rate_up(records: &[&RollRecord]) -> Option<f64> {
+    let mut pulls: HashMap<&str, (u32, bool)> = HashMap::new();
+
+    for record in records {
+        let (count, found) = pulls.entry(&record.banner).or_insert((0, false));
+
+        for pull in record.pulls.iter() {
+            if *found || *count >= SPARK_COST {
+                break;
+            }
+
+            *count += 1;
+            *found = pull.rate_up;
+        }
+    }
+
+    let counts: Vec<u32> = pulls
+        .values()
+        .filter(|&&(count, found)| found || count >= SPARK_COST)
+        .map(|&(count, _)| count)
+        .collect();
+    let total: u32 = counts.iter().sum();
+
+    if counts.is_empty() || total < MIN_PULLS {
+        None
+    } else {
+        Some(total as f64 / counts.len() as f64)
+    }
+}
+
+fn format_stats(stats: &RollStats, expected: Option<f32>) -> String {
+    let expected = expected
+        .map(|rate| format!(" (expected {}%)", rate))
+        .unwrap_or_default();
+
+    format!(
+        "Pulls: {} (3★ {} / 2★ {} / 1★ {})\n3★ rate: {:.2}%{}\nRate-up: {}\nLongest drought: {} pulls",
+        stats.pulls,
+        stats.three,
+        stats.two,
+        stats.one,
+        stats.three_star_rate(),
+        expected,
+        stats.rate_up,
+        stats.longest_drought
+    )
+}
+
+/// Removes user mentions from a command's arguments
+pub(crate) fn strip_mentions(args: &str) -> String {
+    args.split_whitespace()
+        .filter(|word| !(word.starts_with("<@") && word.ends_with('>')))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 10-roll on `banner` whose pulls at the `rate_ups` positions are rate-up students
+    fn ten_roll(banner: &str, rate_ups: &[usize]) -> RollRecord {
+        RollRecord {
+            banner: banner.to_string(),
+            time: Utc::now(),
+            guild: None,
+            pulls: (0..10)
+                .map(|i| Pull {
+                    name: if rate_ups.contains(&i) {
+                        "ヒナ(水着)".to_string()
+                    } else {
+                        "モモイ".to_string()
+                    },
+                    rarity: if rate_ups.contains(&i) { 3 } else { 1 },
+                    rate_up: rate_ups.contains(&i),
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn counts_pulls_until_the_first_rate_up() {
+        let records = [
+            ten_roll("summer", &[]),
+            ten_roll("summer", &[6, 8]),
+            ten_roll("summer", &[]),
+        ];
+        let records: Vec<&RollRecord> = records.iter().collect();
+
+        assert_eq!(pulls_to_rate_up(&records), Some(17.0));
+    }
+
+    #[test]
+    fn a_banner_without_rate_ups_counts_as_a_spark() {
+        let mut records = vec![ten_roll("summer", &[3])];
+        records.extend((0..25).map(|_| ten_roll("festival", &[])));
+        let records: Vec<&RollRecord> = records.iter().collect();
+
+        let sparks = SPARK_COST as f64;
+        assert_eq!(pulls_to_rate_up(&records), Some((4.0 + sparks) / 2.0));
+    }
+
+    #[test]
+    fn skips_banners_with_too_few_pulls() {
+        let records = [
+            ten_roll("summer", &[]),
+            ten_roll("summer", &[1]),
+            ten_roll("festival", &[]),
+        ];
+        let records: Vec<&RollRecord> = records.iter().collect();
+
+        assert_eq!(pulls_to_rate_up(&records), Some(12.0));
+        assert_eq!(pulls_to_rate_up(&records[2..]), None);
+    }
+
+    #[test]
+    fn needs_enough_pulls_overall() {
+        let records = [ten_roll("summer", &[2])];
+        let records: Vec<&RollRecord> = records.iter().collect();
+
+        assert_eq!(pulls_to_rate_up(&records), None);
+    }
+}
@@ -0,0 +1,377 @@
+use crate::banner::Region;
+use crate::fair::FairBanner;
+use blue_gacha::gacha::Rarity;
+use blue_gacha::i18n::Language;
+use blue_gacha::student::Student;
+use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{GuildId, UserId};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+const DEFAULT_STATE_PATH: &str = "state.json";
+/// How often changes to the store are written to disk
+const SAVE_INTERVAL: Duration = Duration::from_secs(5);
+/// How many rolls are kept in a user's history. Older ones are dropped
+pub const MAX_HISTORY: usize = 1000;
+
+lazy_static! {
+    static ref STORE: Mutex<Store> = Mutex::new(Store::default());
+}
+
+/// Whether the store changed since it was last saved
+static DIRTY: AtomicBool = AtomicBool::new(false);
+
+pub enum StoreError {
+    Io(PathBuf, io::Error),
+    Parse(PathBuf, serde_json::Error),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StoreError::Io(path, err) => write!(f, "Failed to read {}: {}", path.display(), err),
+            StoreError::Parse(path, err) => {
+                write!(f, "Failed to parse {}: {}", path.display(), err)
+            }
+        }
+    }
+}
+
+/// Everything arona remembers between restarts
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Store {
+    #[serde(default)]
+    pub users: HashMap<u64, UserData>,
+    #[serde(default)]
+    pub guilds: HashMap<u64, GuildSettings>,
+    /// Every roll done in fairness mode, so they can be verified later
+    #[serde(default)]
+    pub fair_rolls: Vec<FairRoll>,
+    /// Every banner a fair roll was done on, keyed by [`FairBanner::hash`]. Banners are shared
+    /// between rolls rather than copied into each of them
+    #[serde(default)]
+    pub fair_banners: HashMap<String, FairBanner>,
+}
+
+/// A roll driven by a seed which was committed to before the roll was requested
+#[derive(Clone, Serialize, Deserialize)]
+pub struct FairRoll {
+    pub id: u64,
+    pub user: u64,
+    /// The id of the banner that was rolled on
+    pub banner: String,
+    pub count: u32,
+    /// The server seed, hex encoded
+    pub seed: String,
+    /// The id of the message which requested the roll
+    pub nonce: u64,
+    pub time: DateTime<Utc>,
+    /// The Japanese names of the pulled students
+    pub results: Vec<String>,
+    /// The hash of what the banner looked like when it was rolled on, see
+    /// [`Store::fair_banners`]. Missing from rolls saved before banners were recorded with them
+    #[serde(default)]
+    pub banner_hash: Option<String>,
+}
+
+/// Per-server settings
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct GuildSettings {
+    /// Whether rolls cost pyroxene in this server
+    #[serde(default)]
+    pub economy: bool,
+    /// The game server whose banners are used in this server
+    #[serde(default)]
+    pub region: Region,
+    /// The language names are shown in, unless a user picked their own
+    #[serde(default)]
+    pub language: Option<Language>,
+}
+
+/// Everything arona remembers about a single Discord user
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct UserData {
+    /// Recruitment points, keyed by banner id
+    #[serde(default)]
+    pub points: HashMap<String, u32>,
+    /// Every student this user has recruited, keyed by their Japanese name
+    #[serde(default)]
+    pub collection: HashMap<String, OwnedStudent>,
+    /// Eleph of every student, keyed by their Japanese name
+    #[serde(default)]
+    pub eleph: HashMap<String, u32>,
+    /// Pyroxene of this user in every server with an economy, keyed by guild id
+    #[serde(default)]
+    pub wallets: HashMap<u64, Wallet>,
+    /// The last [`MAX_HISTORY`] rolls and 10-rolls this user has done, oldest first
+    #[serde(default)]
+    pub history: Vec<RollRecord>,
+    /// The hex encoded server seed of this user's next fair roll
+    #[serde(default)]
+    pub fair_seed: Option<String>,
+    /// The language names are shown in for this user, overriding their server's
+    #[serde(default)]
+    pub language: Option<Language>,
+}
+
+/// A user's pyroxene in a single server
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub struct Wallet {
+    #[serde(default)]
+    pub pyroxene: u32,
+    /// When the user last claimed their daily pyroxene in this server
+    #[serde(default)]
+    pub last_daily: Option<DateTime<Utc>>,
+}
+
+/// The result of a single roll or 10-roll
+#[derive(Clone, Serialize, Deserialize)]
+pub struct RollRecord {
+    /// The id of the banner that was rolled on
+    pub banner: String,
+    pub time: DateTime<Utc>,
+    /// The server the roll happened in, if it wasn't in a DM
+    #[serde(default)]
+    pub guild: Option<u64>,
+    pub pulls: Vec<Pull>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Pull {
+    /// The student's Japanese name
+    pub name: String,
+    pub rarity: u8,
+    pub rate_up: bool,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OwnedStudent {
+    pub count: u32,
+    pub first_pulled: DateTime<Utc>,
+    /// Star level raised with Eleph. Students start at their rarity, which 0 stands in for
+    #[serde(default)]
+    pub stars: u8,
+}
+
+impl OwnedStudent {
+    pub fn stars(&self, rarity: Rarity) -> u8 {
+        self.stars.max(rarity_level(rarity))
+    }
+}
+
+impl UserData {
+    pub fn points(&self, banner_id: &str) -> u32 {
+        self.points.get(banner_id).copied().unwrap_or(0)
+    }
+
+    /// Adds recruitment points to a banner and returns the new total
+    pub fn add_points(&mut self, banner_id: &str, amount: u32) -> u32 {
+        let points = self.points.entry(banner_id.to_string()).or_insert(0);
+        *points += amount;
+        *points
+    }
+
+    /// Adds a recruited student to the user's collection, returning whether they were a duplicate
+    pub fn record(&mut self, student: &Student, now: DateTime<Utc>) -> bool {
+        let owned = self
+            .collection
+            .entry(student.name.to_string())
+            .or_insert(OwnedStudent {
+                count: 0,
+                first_pulled: now,
+                stars: 0,
+            });
+
+        owned.count += 1;
+        owned.count > 1
+    }
+
+    pub fn eleph(&self, name: &str) -> u32 {
+        self.eleph.get(name).copied().unwrap_or(0)
+    }
+
+    pub fn add_eleph(&mut self, name: &str, amount: u32) -> u32 {
+        let eleph = self.eleph.entry(name.to_string()).or_insert(0);
+        *eleph += amount;
+        *eleph
+    }
+
+    /// Appends a roll to the history, dropping the oldest rolls past [`MAX_HISTORY`]
+    pub fn wallet(&mut self, guild_id: GuildId) -> &mut Wallet {
+        self.wallets.entry(guild_id.0).or_default()
+    }
+
+    pub fn push_history(&mut self, record: RollRecord) {
+        self.history.push(record);
+
+        if self.history.len() > MAX_HISTORY {
+            let excess = self.history.len() - MAX_HISTORY;
+            self.history.drain(..excess);
+        }
+    }
+
+    /// Takes `amount` recruitment points from a banner if there are enough of them
+    pub fn spend_points(&mut self, banner_id: &str, amount: u32) -> bool {
+        match self.points.get_mut(banner_id) {
+            Some(points) if *points >= amount => {
+                *points -= amount;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// The star level a student of `rarity` starts at
+pub fn rarity_level(rarity: Rarity) -> u8 {
+    match rarity {
+        Rarity::One => 1,
+        Rarity::Two => 2,
+        Rarity::Three => 3,
+    }
+}
+
+/// Where the store is saved. Can be overridden with `ARONA_STATE`
+pub fn state_path() -> PathBuf {
+    env::var("ARONA_STATE")
+        .map(PathBuf::from)
+        .unwrap_or_else(|_| PathBuf::from(DEFAULT_STATE_PATH))
+}
+
+/// Returns a copy of everything arona knows about a user
+pub fn user(id: UserId) -> UserData {
+    match STORE.lock() {
+        Ok(store) => store.users.get(&id.0).cloned().unwrap_or_default(),
+        Err(_) => {
+            error!("The store's lock was poisoned");
+            UserData::default()
+        }
+    }
+}
+
+/// Returns a copy of a server's settings
+pub fn guild(id: GuildId) -> GuildSettings {
+    match STORE.lock() {
+        Ok(store) => store.guilds.get(&id.0).cloned().unwrap_or_default(),
+        Err(_) => {
+            error!("The store's lock was poisoned");
+            GuildSettings::default()
+        }
+    }
+}
+
+/// Runs `f` with mutable access to a server's settings. See [`update`]
+pub fn update_guild<T>(id: GuildId, f: impl FnOnce(&mut GuildSettings) -> T) -> T {
+    update(|store| f(store.guilds.entry(id.0).or_default()))
+}
+
+/// Runs `f` with mutable access to a user's data. See [`update`]
+pub fn update_user<T>(id: UserId, f: impl FnOnce(&mut UserData) -> T) -> T {
+    update(|store| f(store.users.entry(id.0).or_default()))
+}
+
+/// Runs `f` with read-only access to the whole store
+pub fn read<T>(f: impl FnOnce(&Store) -> T) -> T {
+    match STORE.lock() {
+        Ok(store) => f(&store),
+        Err(poisoned) => {
+            error!("The store's lock was poisoned");
+            f(&poisoned.into_inner())
+        }
+    }
+}
+
+/// Runs `f` with mutable access to the whole store. The change is saved by [`persist`]
+pub fn update<T>(f: impl FnOnce(&mut Store) -> T) -> T {
+    let mut store = match STORE.lock() {
+        Ok(store) => store,
+        Err(poisoned) => {
+            error!("The store's lock was poisoned");
+            poisoned.into_inner()
+        }
+    };
+
+    let ret = f(&mut store);
+    DIRTY.store(true, Ordering::Release);
+    ret
+}
+
+/// Loads the store from [`state_path`]. Has to be called before the bot starts.
+///
+/// A missing file starts a fresh store, but one which can't be parsed is an error, since
+/// starting over would throw away everyone's data on the next save.
+pub fn init() -> Result<(), StoreError> {
+    let path = state_path();
+
+    let store = match fs::read_to_string(&path) {
+        Ok(text) => {
+            let store: Store =
+                serde_json::from_str(&text).map_err(|err| StoreError::Parse(path.clone(), err))?;
+            info!("Loaded the store from {}", path.display());
+            store
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            warn!("{} does not exist, starting fresh", path.display());
+            Store::default()
+        }
+        Err(err) => return Err(StoreError::Io(path, err)),
+    };
+
+    match STORE.lock() {
+        Ok(mut current) => *current = store,
+        Err(poisoned) => *poisoned.into_inner() = store,
+    }
+
+    Ok(())
+}
+
+/// Saves the store every [`SAVE_INTERVAL`] if it changed. Runs forever
+pub async fn persist() {
+    loop {
+        tokio::time::sleep(SAVE_INTERVAL).await;
+        flush().await;
+    }
+}
+
+/// Saves the store now if it changed since it was last saved
+pub async fn flush() {
+    if !DIRTY.swap(false, Ordering::AcqRel) {
+        return;
+    }
+
+    // Serializing takes far longer than copying, so only the copy holds up other commands
+    let snapshot = read(Store::clone);
+    if let Err(err) = tokio::task::spawn_blocking(move || save(&snapshot)).await {
+        error!("Failed to save the store: {}", err);
+    }
+}
+
+fn save(store: &Store) {
+    let json = match serde_json::to_string(store) {
+        Ok(json) => json,
+        Err(err) => {
+            error!("Failed to serialize the store: {}", err);
+            DIRTY.store(true, Ordering::Release);
+            return;
+        }
+    };
+
+    let path = state_path();
+    let tmp_path = path.with_extension("json.tmp");
+
+    // Write to a temporary file first so a crash never leaves a half-written store behind
+    if let Err(err) = fs::write(&tmp_path, json).and_then(|_| fs::rename(&tmp_path, &path)) {
+        error!("Failed to save the store to {}: {}", path.display(), err);
+        DIRTY.store(true, Ordering::Release);
+    }
+}
@@ -0,0 +1,489 @@
+use crate::banner::{all_students, find_student, Region, StudentInfo};
+use crate::general::BLUE_ARCHIVE_BLUE;
+use crate::language::{display_name, preferred_language, secondary, translate};
+use crate::lookup::select_student;
+use crate::recruitment::{get_rarity_colour, get_rarity_stars, page_url, portrait_url, CDN_URL};
+use crate::store::rarity_level;
+use blue_gacha::gacha::Rarity;
+use log::info;
+use serde::Deserialize;
+use serenity::client::Context;
+use serenity::framework::standard::{Args, CommandResult};
+use serenity::model::channel::Message;
+use std::fmt;
+
+/// Everything about a student that the gacha itself doesn't need. Any of it can be left out
+#[derive(Debug, Default, Clone, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub school: Option<String>,
+    #[serde(default)]
+    pub club: Option<String>,
+    #[serde(default)]
+    pub role: Option<Role>,
+    #[serde(default)]
+    pub attack: Option<AttackType>,
+    #[serde(default)]
+    pub armor: Option<ArmorType>,
+    #[serde(default)]
+    pub position: Option<Position>,
+    /// The kind of weapon the student uses, e.g. `AR` or `SMG`
+    #[serde(default)]
+    pub weapon: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Striker,
+    Special,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttackType {
+    Explosive,
+    Piercing,
+    Mystic,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArmorType {
+    Light,
+    Heavy,
+    Special,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Position {
+    Front,
+    Middle,
+    Back,
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Striker => write!(f, "Striker"),
+            Role::Special => write!(f, "Special"),
+        }
+    }
+}
+
+impl fmt::Display for AttackType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AttackType::Explosive => write!(f, "Explosive"),
+            AttackType::Piercing => write!(f, "Piercing"),
+            AttackType::Mystic => write!(f, "Mystic"),
+        }
+    }
+}
+
+impl fmt::Display for ArmorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArmorType::Light => write!(f, "Light"),
+            ArmorType::Heavy => write!(f, "Heavy"),
+            ArmorType::Special => write!(f, "Special"),
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Position::Front => write!(f, "Front"),
+            Position::Middle => write!(f, "Middle"),
+            Position::Back => write!(f, "Back"),
+        }
+    }
+}
+
+/// What `!students` can filter by
+const FILTER_KEYS: [&str; 9] = [
+    "rarity", "school", "club", "role", "attack", "armor", "position", "weapon", "limited",
+];
+
+pub async fn student(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    let query = args.rest().trim();
+    if query.is_empty() {
+        msg.reply(ctx, "Usage: `!student <name>`").await?;
+        return Ok(());
+    }
+    info!("{} requested the profile of {}", author_name, query);
+
+    let info = match select_student(ctx, msg, query).await? {
+        Some(info) => info,
+        None => return Ok(()),
+    };
+    let student = &info.student;
+    let profile = &info.profile;
+
+    let language = preferred_language(msg);
+    let secondary_name = secondary(&student.name, language).unwrap_or_default();
+    let img_url = portrait_url(student);
+    let title_url = page_url(student);
+    let icon_url = format!("{}/Icons/icon-brand.png", CDN_URL);
+
+    let fields = vec![
+        ("School", profile.school.clone()),
+        ("Club", profile.club.clone()),
+        ("Role", profile.role.map(|role| role.to_string())),
+        (
+            "Attack Type",
+            profile.attack.map(|attack| attack.to_string()),
+        ),
+        ("Armor Type", profile.armor.map(|armor| armor.to_string())),
+        (
+            "Position",
+            profile.position.map(|position| position.to_string()),
+        ),
+        ("Weapon", profile.weapon.clone()),
+    ];
+    let fields: Vec<(&str, String, bool)> = fields
+        .into_iter()
+        .map(|(name, value)| (name, value.unwrap_or_else(|| "Unknown".to_string()), true))
+        .collect();
+
+    let released: Vec<String> = [Region::Japan, Region::Global]
+        .iter()
+        .filter_map(|region| {
+            info.release
+                .get(region)
+                .map(|date| format!("{}: {}", region, date.format("%Y-%m-%d")))
+        })
+        .collect();
+    let mut description = format!("{}\t{}", secondary_name, get_rarity_stars(student.rarity));
+    if info.limited {
+        description.push_str("\nLimited");
+    }
+    if let Some(base) = &info.base {
+        let base = match find_student(base) {
+            Some(base) => display_name(&base.name, language),
+            None => base.clone(),
+        };
+        description.push_str(&format!("\nAlternate version of {}", base));
+    }
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .image(img_url)
+                    .title(translate(&student.name, language))
+                    .description(description)
+                    .url(title_url)
+                    .fields(fields)
+                    .footer(|footer| {
+                        footer
+                            .icon_url(icon_url)
+                            .text("Image Source: https://thearchive.gg")
+                    })
+                    .colour(get_rarity_colour(student.rarity));
+
+                if !released.is_empty() {
+                    embed.field("Released", released.join("\n"), false);
+                }
+
+                embed
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+pub async fn students(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    const STUDENTS_PER_PAGE: usize = 20;
+
+    let author_name = format!("{}#{}", msg.author.name, msg.author.discriminator);
+
+    // Filters are `key:value` (with `value,value` matching either), and a number picks the page
+    let mut filters: Vec<(String, String)> = Vec::new();
+    let mut page = 1;
+    for word in args.rest().split_whitespace() {
+        if let Ok(number) = word.parse::<usize>() {
+            page = number.max(1);
+            continue;
+        }
+
+        match word.split_once(':') {
+            Some((key, value)) if FILTER_KEYS.contains(&key.to_lowercase().as_str()) => {
+                filters.push((key.to_lowercase(), value.to_string()))
+            }
+            _ => {
+                msg.reply(
+                    ctx,
+                    format!(
+                        "アロナ can't filter by `{}`. Filters look like `rarity:3 school:Millennium` and can use {}",
+                        word,
+                        FILTER_KEYS.join(", ")
+                    ),
+                )
+                .await?;
+                return Ok(());
+            }
+        }
+    }
+    let query = filters
+        .iter()
+        .map(|(key, value)| format!("{}:{}", key, value))
+        .collect::<Vec<_>>()
+        .join(" ");
+    info!(
+        "{} requested page {} of the students matching \"{}\"",
+        author_name, page, query
+    );
+
+    let language = preferred_language(msg);
+    let students = all_students();
+    let mut matching: Vec<&StudentInfo> = students
+        .iter()
+        .filter(|info| matches_filters(info, &filters))
+        .collect();
+    matching.sort_by_key(|info| std::cmp::Reverse(info.student.rarity));
+
+    let counts = [Rarity::Three, Rarity::Two, Rarity::One]
+        .iter()
+        .map(|&rarity| {
+            let count = matching
+                .iter()
+                .filter(|info| info.student.rarity == rarity)
+                .count();
+            format!("{} {}", get_rarity_stars(rarity), count)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let page_count = matching.len().div_ceil(STUDENTS_PER_PAGE).max(1);
+    let page = page.min(page_count);
+    let list = matching
+        .chunks(STUDENTS_PER_PAGE)
+        .nth(page - 1)
+        .unwrap_or_default()
+        .iter()
+        .map(|info| {
+            let student = &info.student;
+            format!(
+                "{} {}",
+                display_name(&student.name, language),
+                get_rarity_stars(student.rarity)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let description = match (matching.len(), query.is_empty()) {
+        (0, _) => "No students match".to_string(),
+        (count, true) => format!("{} students", count),
+        (count, false) => format!("{} students match `{}`", count, query),
+    };
+
+    msg.channel_id
+        .send_message(ctx, |m| {
+            m.embed(|embed| {
+                embed
+                    .title("Students")
+                    .description(description)
+                    .field("Rarities", counts, true)
+                    .footer(|footer| {
+                        footer.text(format!(
+                            "Page {}/{} • !students {}<page>",
+                            page,
+                            page_count,
+                            filters
+                                .iter()
+                                .map(|(key, value)| format!("{}:{} ", key, value))
+                                .collect::<String>()
+                        ))
+                    })
+                    .colour(BLUE_ARCHIVE_BLUE);
+
+                if !list.is_empty() {
+                    embed.field("Matching Students", list, false);
+                }
+
+                embed
+            })
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Whether a student matches every `key:value` filter of `!students`, where a value can be a
+/// comma separated list of values to match either of
+fn matches_filters(info: &StudentInfo, filters: &[(String, String)]) -> bool {
+    filters.iter().all(|(key, values)| {
+        values
+            .split(',')
+            .any(|value| filter_matches(info, key, value))
+    })
+}
+
+/// Whether a student matches one of the `key:value` filters of `!students`. Text is compared
+/// ignoring case and spaces, so `club:gamedevelopmentdepartment` works too
+fn filter_matches(info: &StudentInfo, key: &str, value: &str) -> bool {
+    let simplify = |text: &str| -> String {
+        text.chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(char::to_lowercase)
+            .collect()
+    };
+    let value = simplify(value);
+    let matches = |field: Option<String>| {
+        field
+            .map(|field| simplify(&field) == value)
+            .unwrap_or(false)
+    };
+    let profile = &info.profile;
+
+    match key {
+        "rarity" => value == rarity_level(info.student.rarity).to_string(),
+        "school" => matches(profile.school.clone()),
+        "club" => matches(profile.club.clone()),
+        "role" => matches(profile.role.map(|role| role.to_string())),
+        "attack" => matches(profile.attack.map(|attack| attack.to_string())),
+        "armor" => matches(profile.armor.map(|armor| armor.to_string())),
+        "position" => matches(profile.position.map(|position| position.to_string())),
+        "weapon" => matches(profile.weapon.clone()),
+        "limited" => match value.as_str() {
+            "yes" | "true" => info.limited,
+            "no" | "false" => !info.limited,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn students() -> Vec<StudentInfo> {
+        serde_json::from_str(
+            r#"[
+                {
+                    "name": { "translations": { "jpn": "ヒナ", "eng": "Hina" } },
+                    "rarity": 3,
+                    "school": "Gehenna",
+                    "club": "Prefect Team",
+                    "role": "striker",
+                    "attack": "explosive",
+                    "armor": "heavy",
+                    "position": "back",
+                    "weapon": "MG"
+                },
+                {
+                    "name": { "translations": { "jpn": "ヒナ（水着）", "eng": "Hina (Swimsuit)" } },
+                    "rarity": 3,
+                    "limited": true,
+                    "base": "ヒナ",
+                    "image": "Hina_Swimsuit",
+                    "school": "Gehenna",
+                    "club": "Prefect Team",
+                    "role": "striker",
+                    "attack": "mystic",
+                    "armor": "light",
+                    "position": "middle",
+                    "weapon": "MG"
+                },
+                {
+                    "name": { "translations": { "jpn": "ヒビキ", "eng": "Hibiki" } },
+                    "rarity": 3,
+                    "school": "Millennium",
+                    "club": "Engineering Department",
+                    "role": "special",
+                    "attack": "explosive",
+                    "armor": "light",
+                    "position": "back",
+                    "weapon": "MT"
+                },
+                {
+                    "name": { "translations": { "jpn": "モモイ", "eng": "Momoi" } },
+                    "rarity": 1,
+                    "school": "Millennium",
+                    "club": "Game Development Department",
+                    "role": "striker",
+                    "attack": "explosive",
+                    "armor": "heavy",
+                    "position": "back",
+                    "weapon": "SG"
+                },
+                {
+                    "name": { "translations": { "jpn": "セリナ", "eng": "Serina" } },
+                    "rarity": 2,
+                    "school": "Trinity",
+                    "club": "Remedial Knights",
+                    "role": "special",
+                    "attack": "mystic",
+                    "armor": "light",
+                    "position": "back",
+                    "weapon": "HG"
+                }
+            ]"#,
+        )
+        .unwrap()
+    }
+
+    fn matching(query: &str) -> Vec<String> {
+        let filters: Vec<(String, String)> = query
+            .split_whitespace()
+            .filter_map(|word| word.split_once(':'))
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+
+        students()
+            .iter()
+            .filter(|info| matches_filters(info, &filters))
+            .map(|info| info.student.name.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn the_documented_example_matches() {
+        assert_eq!(
+            matching("rarity:3 school:Millennium role:special"),
+            ["ヒビキ"]
+        );
+    }
+
+    #[test]
+    fn every_filter_has_to_match() {
+        assert_eq!(matching("role:special"), ["ヒビキ", "セリナ"]);
+        assert_eq!(matching("school:Millennium role:special"), ["ヒビキ"]);
+        assert!(matching("school:Trinity rarity:3").is_empty());
+    }
+
+    #[test]
+    fn listed_values_match_either() {
+        assert_eq!(matching("school:Trinity"), ["セリナ"]);
+        assert_eq!(
+            matching("school:Trinity,Millennium"),
+            ["ヒビキ", "モモイ", "セリナ"]
+        );
+    }
+
+    #[test]
+    fn values_ignore_case_and_spaces() {
+        assert_eq!(matching("club:gamedevelopmentdepartment"), ["モモイ"]);
+        assert_eq!(matching("club:Game_Development_Department"), ["モモイ"]);
+        assert_eq!(matching("weapon:mg attack:MYSTIC"), ["ヒナ（水着）"]);
+    }
+
+    #[test]
+    fn limited_students_can_be_filtered() {
+        assert_eq!(matching("limited:yes"), ["ヒナ（水着）"]);
+        assert_eq!(matching("limited:no school:Gehenna"), ["ヒナ"]);
+    }
+
+    #[test]
+    fn unknown_keys_match_nobody() {
+        assert!(matching("height:tall").is_empty());
+    }
+}